@@ -1,25 +1,73 @@
 use std::process;
 
+use crate::cover_art::GraphicsProtocol;
+use crate::gradient::Gradient;
+use crate::playlist::RepeatMode;
+use crate::spectrum::{FFT_SIZES, WindowFunction};
+use crate::ui::{SpectrumStyle, Visualizer};
+use crate::waveform::WaveformStyle;
+
 pub struct Config {
-    pub audio_path: String,
-    pub use_visualizer: bool,
+    pub audio_paths: Vec<String>,
+    pub visualizer: Visualizer,
+    pub spectrum_style: SpectrumStyle,
+    pub no_axes: bool,
+    pub mid_side: bool,
+    pub gradient: Gradient,
     pub num_bars: usize,
-    pub smoothing: f32,
+    pub attack_ms: f32,
+    pub decay_ms: f32,
+    pub peak_hold_ms: f32,
+    pub peak_fall: f32,
     pub bass_boost: f32,
+    pub fft_size: usize,
+    pub window: WindowFunction,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub db_floor: f32,
+    pub db_ceiling: f32,
     pub volume_step: f32,
     pub seek_step: i64,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    pub save_playlist: Option<String>,
+    pub cover: GraphicsProtocol,
+    pub waveform: WaveformStyle,
+    pub no_cache: bool,
+    // Set by `apz cache clear`, which does nothing else.
+    pub clear_cache: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            audio_path: String::new(),
-            use_visualizer: false,
+            audio_paths: Vec::new(),
+            visualizer: Visualizer::Waveform,
+            spectrum_style: SpectrumStyle::Blocks,
+            no_axes: false,
+            mid_side: false,
+            gradient: Gradient::Viridis,
             num_bars: 100,
-            smoothing: 0.7,
+            attack_ms: 15.0,
+            decay_ms: 120.0,
+            peak_hold_ms: 400.0,
+            peak_fall: 3.0,
             bass_boost: 1.5,
+            fft_size: 2048,
+            window: WindowFunction::Hann,
+            min_freq: 20.0,
+            max_freq: 20000.0,
+            db_floor: -80.0,
+            db_ceiling: 0.0,
             volume_step: 0.05,
             seek_step: 5,
+            shuffle: false,
+            repeat: RepeatMode::Off,
+            save_playlist: None,
+            cover: GraphicsProtocol::Auto,
+            waveform: WaveformStyle::Simple,
+            no_cache: false,
+            clear_cache: false,
         }
     }
 }
@@ -30,21 +78,90 @@ impl Config {
         let mut config = Config::default();
         let mut i = 1;
 
+        if args.get(1).map(String::as_str) == Some("cache") {
+            if args.get(2).map(String::as_str) == Some("clear") && args.len() == 3 {
+                config.clear_cache = true;
+                return config;
+            }
+            eprintln!("Error: the only cache command is 'cache clear'");
+            Self::print_usage(&args[0]);
+        }
+
         while i < args.len() {
             match args[i].as_str() {
-                "--visualizer" => {
-                    config.use_visualizer = true;
+                "--visualizer" | "--spectrogram" | "--oscilloscope" | "--vectorscope" => {
+                    config.visualizer = match args[i].as_str() {
+                        "--visualizer" => Visualizer::Spectrum,
+                        "--spectrogram" => Visualizer::Spectrogram,
+                        "--oscilloscope" => Visualizer::Oscilloscope,
+                        _ => Visualizer::Vectorscope,
+                    };
                     i += 1;
                 }
+                "--spectrum-style" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --spectrum-style requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.spectrum_style =
+                        SpectrumStyle::parse(&args[i + 1]).unwrap_or_else(|| {
+                            eprintln!("Error: --spectrum-style must be one of blocks, braille");
+                            Self::print_usage(&args[0]);
+                        });
+                    i += 2;
+                }
+                "--no-axes" => {
+                    config.no_axes = true;
+                    i += 1;
+                }
+                "--mid-side" => {
+                    config.mid_side = true;
+                    i += 1;
+                }
+                "--gradient" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --gradient requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.gradient = Gradient::parse(&args[i + 1]).unwrap_or_else(|| {
+                        eprintln!("Error: --gradient must be one of viridis, magma, grayscale");
+                        Self::print_usage(&args[0]);
+                    });
+                    i += 2;
+                }
                 "--bars" => {
                     if i + 1 >= args.len() {
                         eprintln!("Error: --bars requires a value");
                         Self::print_usage(&args[0]);
                     }
-                    config.num_bars = args[i + 1].parse().unwrap_or_else(|_| {
-                        eprintln!("Error: --bars must be a positive integer");
+                    config.num_bars = args[i + 1]
+                        .parse()
+                        .ok()
+                        .filter(|&bars: &usize| bars > 0)
+                        .unwrap_or_else(|| {
+                            eprintln!("Error: --bars must be a positive integer");
+                            Self::print_usage(&args[0]);
+                        });
+                    i += 2;
+                }
+                "--attack" | "--decay" | "--peak-hold" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: {} requires a value", args[i]);
                         Self::print_usage(&args[0]);
-                    });
+                    }
+                    let ms = args[i + 1]
+                        .parse()
+                        .ok()
+                        .filter(|&ms: &f32| ms >= 0.0)
+                        .unwrap_or_else(|| {
+                            eprintln!("Error: {} must be a time in milliseconds", args[i]);
+                            Self::print_usage(&args[0]);
+                        });
+                    match args[i].as_str() {
+                        "--attack" => config.attack_ms = ms,
+                        "--decay" => config.decay_ms = ms,
+                        _ => config.peak_hold_ms = ms,
+                    }
                     i += 2;
                 }
                 "--smoothing" => {
@@ -52,11 +169,33 @@ impl Config {
                         eprintln!("Error: --smoothing requires a value");
                         Self::print_usage(&args[0]);
                     }
-                    config.smoothing = args[i + 1].parse().unwrap_or_else(|_| {
+                    let smoothing: f32 = args[i + 1].parse().unwrap_or_else(|_| {
                         eprintln!("Error: --smoothing must be a float between 0.0 and 1.0");
                         Self::print_usage(&args[0]);
                     });
-                    config.smoothing = config.smoothing.clamp(0.0, 1.0);
+                    // The factor used to be applied once per frame, about
+                    // every 100 ms, on the way up and down alike.
+                    let keep = smoothing.clamp(0.0, 0.99);
+                    config.decay_ms = (-100.0 / keep.ln()).round();
+                    eprintln!(
+                        "Warning: --smoothing is deprecated, use --decay {} instead",
+                        config.decay_ms
+                    );
+                    i += 2;
+                }
+                "--peak-fall" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --peak-fall requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.peak_fall = args[i + 1]
+                        .parse()
+                        .ok()
+                        .filter(|&fall: &f32| fall > 0.0)
+                        .unwrap_or_else(|| {
+                            eprintln!("Error: --peak-fall must be a positive float");
+                            Self::print_usage(&args[0]);
+                        });
                     i += 2;
                 }
                 "--bass-boost" => {
@@ -70,6 +209,72 @@ impl Config {
                     });
                     i += 2;
                 }
+                "--fft-size" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --fft-size requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.fft_size = args[i + 1]
+                        .parse()
+                        .ok()
+                        .filter(|size: &usize| size.is_power_of_two() && FFT_SIZES.contains(size))
+                        .unwrap_or_else(|| {
+                            eprintln!(
+                                "Error: --fft-size must be a power of two from {} to {}",
+                                FFT_SIZES.start(),
+                                FFT_SIZES.end()
+                            );
+                            Self::print_usage(&args[0]);
+                        });
+                    i += 2;
+                }
+                "--window" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --window requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.window = WindowFunction::parse(&args[i + 1]).unwrap_or_else(|| {
+                        eprintln!("Error: --window must be one of hann, blackman-harris");
+                        Self::print_usage(&args[0]);
+                    });
+                    i += 2;
+                }
+                "--min-freq" | "--max-freq" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: {} requires a value", args[i]);
+                        Self::print_usage(&args[0]);
+                    }
+                    let freq: f32 = args[i + 1]
+                        .parse()
+                        .ok()
+                        .filter(|&freq: &f32| freq > 0.0)
+                        .unwrap_or_else(|| {
+                            eprintln!("Error: {} must be a positive frequency in Hz", args[i]);
+                            Self::print_usage(&args[0]);
+                        });
+                    if args[i] == "--min-freq" {
+                        config.min_freq = freq;
+                    } else {
+                        config.max_freq = freq;
+                    }
+                    i += 2;
+                }
+                "--db-floor" | "--db-ceiling" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: {} requires a value", args[i]);
+                        Self::print_usage(&args[0]);
+                    }
+                    let db = args[i + 1].parse().unwrap_or_else(|_| {
+                        eprintln!("Error: {} must be a level in dBFS", args[i]);
+                        Self::print_usage(&args[0]);
+                    });
+                    if args[i] == "--db-floor" {
+                        config.db_floor = db;
+                    } else {
+                        config.db_ceiling = db;
+                    }
+                    i += 2;
+                }
                 "--volume-step" => {
                     if i + 1 >= args.len() {
                         eprintln!("Error: --volume-step requires a value");
@@ -93,16 +298,60 @@ impl Config {
                     });
                     i += 2;
                 }
+                "--shuffle" => {
+                    config.shuffle = true;
+                    i += 1;
+                }
+                "--repeat" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --repeat requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.repeat = RepeatMode::parse(&args[i + 1]).unwrap_or_else(|| {
+                        eprintln!("Error: --repeat must be one of off, one, all");
+                        Self::print_usage(&args[0]);
+                    });
+                    i += 2;
+                }
+                "--save-playlist" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --save-playlist requires a path");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.save_playlist = Some(args[i + 1].clone());
+                    i += 2;
+                }
+                "--cover" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --cover requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.cover = GraphicsProtocol::parse(&args[i + 1]).unwrap_or_else(|| {
+                        eprintln!("Error: --cover must be one of auto, kitty, sixel, blocks, off");
+                        Self::print_usage(&args[0]);
+                    });
+                    i += 2;
+                }
+                "--waveform" => {
+                    if i + 1 >= args.len() {
+                        eprintln!("Error: --waveform requires a value");
+                        Self::print_usage(&args[0]);
+                    }
+                    config.waveform = WaveformStyle::parse(&args[i + 1]).unwrap_or_else(|| {
+                        eprintln!("Error: --waveform must be one of simple, enhanced");
+                        Self::print_usage(&args[0]);
+                    });
+                    i += 2;
+                }
+                "--no-cache" => {
+                    config.no_cache = true;
+                    i += 1;
+                }
                 "--help" | "-h" => {
                     Self::print_usage(&args[0]);
                 }
                 arg if !arg.starts_with('-') => {
-                    if config.audio_path.is_empty() {
-                        config.audio_path = arg.to_string();
-                    } else {
-                        eprintln!("Error: Multiple audio files specified");
-                        Self::print_usage(&args[0]);
-                    }
+                    config.audio_paths.push(arg.to_string());
                     i += 1;
                 }
                 _ => {
@@ -112,7 +361,16 @@ impl Config {
             }
         }
 
-        if config.audio_path.is_empty() {
+        if config.min_freq >= config.max_freq {
+            eprintln!("Error: --min-freq must be below --max-freq");
+            Self::print_usage(&args[0]);
+        }
+        if config.db_floor >= config.db_ceiling {
+            eprintln!("Error: --db-floor must be below --db-ceiling");
+            Self::print_usage(&args[0]);
+        }
+
+        if config.audio_paths.is_empty() {
             eprintln!("Error: No audio file specified");
             Self::print_usage(&args[0]);
         }
@@ -121,15 +379,49 @@ impl Config {
     }
 
     fn print_usage(program: &str) -> ! {
-        eprintln!("Usage: {} [OPTIONS] <audio_file>", program);
+        eprintln!(
+            "Usage: {} [OPTIONS] <audio_file|directory|playlist>...",
+            program
+        );
+        eprintln!("       {} cache clear", program);
         eprintln!("\nSupported formats: MP3, WAV, FLAC, OGG, AAC/M4A");
+        eprintln!("Playlists: M3U, M3U8, PLS, CUE");
+        eprintln!("Directories are searched recursively and played in name order.");
         eprintln!("\nOptions:");
-        eprintln!("  --visualizer           Enable live spectrum analyzer");
+        eprintln!("  --visualizer           Start with the live spectrum analyzer (also V)");
+        eprintln!("  --spectrum-style <s>   Spectrum bars: blocks, braille (default: blocks)");
+        eprintln!("  --no-axes              Hide the spectrum's frequency and dB scales (also A)");
+        eprintln!("  --spectrogram          Show a scrolling spectrogram instead");
+        eprintln!("  --oscilloscope         Show the waveform as it plays instead");
+        eprintln!("  --vectorscope          Plot left against right instead");
+        eprintln!("  --mid-side             Plot mid against side in the vectorscope (also X)");
+        eprintln!(
+            "  --gradient <name>      Spectrogram colours: viridis, magma, grayscale (default: viridis)"
+        );
         eprintln!("  --bars <n>             Number of frequency bars (default: 100)");
-        eprintln!("  --smoothing <f>        Smoothing factor 0.0-1.0 (default: 0.7)");
-        eprintln!("  --bass-boost <f>       Bass boost multiplier (default: 1.5)");
+        eprintln!("  --attack <ms>          Time for bars to rise (default: 15)");
+        eprintln!("  --decay <ms>           Time for bars to fall (default: 120)");
+        eprintln!("  --peak-hold <ms>       Time peak caps wait before dropping (default: 400)");
+        eprintln!("  --peak-fall <f>        Peak cap gravity, in heights/s² (default: 3.0)");
+        eprintln!(
+            "  --bass-boost <f>       Bass boost multiplier without the dB scale (default: 1.5)"
+        );
+        eprintln!("  --fft-size <n>         FFT size, a power of two 1024-16384 (default: 2048)");
+        eprintln!("  --window <fn>          FFT window: hann, blackman-harris (default: hann)");
+        eprintln!("  --min-freq <hz>        Lowest frequency shown (default: 20)");
+        eprintln!("  --max-freq <hz>        Highest frequency shown (default: 20000)");
+        eprintln!("  --db-floor <db>        Level shown as an empty bar (default: -80)");
+        eprintln!("  --db-ceiling <db>      Level shown as a full bar (default: 0)");
         eprintln!("  --volume-step <f>      Volume adjustment step (default: 0.05)");
         eprintln!("  --seek-step <n>        Seek step in seconds (default: 5)");
+        eprintln!("  --shuffle              Play the queue in random order");
+        eprintln!("  --repeat <mode>        Repeat mode: off, one, all (default: off)");
+        eprintln!("  --save-playlist <path> Write the queue to an M3U8 playlist (also used by W)");
+        eprintln!(
+            "  --cover <mode>         Cover art: auto, kitty, sixel, blocks, off (default: auto)"
+        );
+        eprintln!("  --waveform <style>     Waveform style: simple, enhanced (default: simple)");
+        eprintln!("  --no-cache             Don't read or write the waveform cache");
         eprintln!("  -h, --help             Show this help message");
         eprintln!("\nControls:");
         eprintln!("  Space    - Play/pause");
@@ -137,6 +429,26 @@ impl Config {
         eprintln!("  ←/→      - Seek backward/forward");
         eprintln!("  ↑/↓      - Volume up/down");
         eprintln!("  R        - Restart");
+        eprintln!("  N/>      - Next track");
+        eprintln!("  P/<      - Previous track (restarts if >3s in)");
+        eprintln!("  S        - Toggle shuffle");
+        eprintln!("  L        - Cycle repeat mode (off/all/one)");
+        eprintln!("  W        - Save queue as M3U8");
+        eprintln!("  I        - Toggle track info");
+        eprintln!("  Y        - Toggle lyrics");
+        eprintln!("  V        - Cycle waveform/spectrum/spectrogram/oscilloscope/vectorscope");
+        eprintln!("  F        - Toggle full-screen visualizer");
+        eprintln!(
+            "  M        - Switch waveform (simple/enhanced) or spectrum (blocks/braille) style"
+        );
+        eprintln!("  C        - Toggle per-channel waveform/split spectrum");
+        eprintln!("  G        - Cycle spectrogram colours");
+        eprintln!("  X        - Switch the vectorscope between L/R and M/S");
+        eprintln!("  A        - Toggle the spectrum's scales");
+        eprintln!("  [/]      - Move the spectrum cursor");
+        eprintln!("  +/-      - Zoom waveform in/out");
+        eprintln!("  T        - Toggle keeping the playhead centred");
+        eprintln!("  PgUp/Dn  - Scroll unsynced lyrics");
         process::exit(1);
     }
 }
@@ -1,44 +1,173 @@
-use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
 use std::time::Duration;
 
 use crate::player::Player;
+use crate::ui::{ClickTarget, UIState};
+
+/// Lines moved by Page Up/Down in unsynchronized lyrics.
+const LYRICS_SCROLL_STEP: isize = 5;
 
 pub enum ControlAction {
     Quit,
+    SavePlaylist,
+    ToggleInfo,
+    ToggleLyrics,
+    ToggleStyle,
+    ToggleChannels,
+    CycleVisualizer,
+    ToggleFullscreen,
+    CycleGradient,
+    ToggleMidSide,
+    ToggleAxes,
+    MoveSpectrumCursor(isize),
+    SetSpectrumCursor(usize),
+    ZoomWaveform(i32),
+    ToggleFollow,
+    ScrollLyrics(isize),
     Continue,
 }
 
-pub fn handle_input(player: &Player) -> Result<ControlAction, Box<dyn std::error::Error>> {
-    if event::poll(Duration::from_millis(100))?
-        && let Event::Key(KeyEvent { code, .. }) = event::read()?
-    {
-        match code {
-            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
-                return Ok(ControlAction::Quit);
-            }
-            KeyCode::Char(' ') => {
-                player.toggle_play_pause();
-            }
-            KeyCode::Left => {
-                player.seek(-player.seek_step);
-            }
-            KeyCode::Right => {
-                player.seek(player.seek_step);
-            }
-            KeyCode::Up => {
-                let new_volume = (player.volume() + player.volume_step).min(1.0);
-                player.set_volume(new_volume);
-            }
-            KeyCode::Down => {
-                let new_volume = (player.volume() - player.volume_step).max(0.0);
-                player.set_volume(new_volume);
-            }
-            KeyCode::Char('r') | KeyCode::Char('R') => {
-                player.restart();
-            }
-            _ => {}
+pub fn handle_input(
+    player: &mut Player,
+    ui_state: &UIState,
+) -> Result<ControlAction, Box<dyn std::error::Error>> {
+    if !event::poll(Duration::from_millis(100))? {
+        return Ok(ControlAction::Continue);
+    }
+
+    match event::read()? {
+        Event::Key(KeyEvent { code, .. }) => Ok(handle_key(code, player)),
+        Event::Mouse(mouse) => Ok(handle_mouse(mouse, player, ui_state)),
+        _ => Ok(ControlAction::Continue),
+    }
+}
+
+fn handle_key(code: KeyCode, player: &mut Player) -> ControlAction {
+    match code {
+        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
+            return ControlAction::Quit;
+        }
+        KeyCode::Char(' ') => {
+            player.toggle_play_pause();
+        }
+        KeyCode::Left => {
+            player.seek(-player.seek_step);
+        }
+        KeyCode::Right => {
+            player.seek(player.seek_step);
+        }
+        KeyCode::Up => {
+            let new_volume = (player.volume() + player.volume_step).min(1.0);
+            player.set_volume(new_volume);
+        }
+        KeyCode::Down => {
+            let new_volume = (player.volume() - player.volume_step).max(0.0);
+            player.set_volume(new_volume);
+        }
+        KeyCode::Char('r') | KeyCode::Char('R') => {
+            player.restart();
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Char('>') => {
+            player.next_track();
+        }
+        KeyCode::Char('p') | KeyCode::Char('P') | KeyCode::Char('<') => {
+            player.previous_track();
+        }
+        KeyCode::Char('s') | KeyCode::Char('S') => {
+            player.toggle_shuffle();
+        }
+        KeyCode::Char('l') | KeyCode::Char('L') => {
+            player.cycle_repeat();
+        }
+        KeyCode::Char('w') | KeyCode::Char('W') => {
+            return ControlAction::SavePlaylist;
+        }
+        KeyCode::Char('i') | KeyCode::Char('I') => {
+            return ControlAction::ToggleInfo;
+        }
+        KeyCode::Char('y') | KeyCode::Char('Y') => {
+            return ControlAction::ToggleLyrics;
+        }
+        KeyCode::Char('m') | KeyCode::Char('M') => {
+            return ControlAction::ToggleStyle;
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') => {
+            return ControlAction::ToggleChannels;
+        }
+        KeyCode::Char('v') | KeyCode::Char('V') => {
+            return ControlAction::CycleVisualizer;
+        }
+        KeyCode::Char('f') | KeyCode::Char('F') => {
+            return ControlAction::ToggleFullscreen;
+        }
+        KeyCode::Char('g') | KeyCode::Char('G') => {
+            return ControlAction::CycleGradient;
+        }
+        KeyCode::Char('x') | KeyCode::Char('X') => {
+            return ControlAction::ToggleMidSide;
+        }
+        KeyCode::Char('a') | KeyCode::Char('A') => {
+            return ControlAction::ToggleAxes;
+        }
+        KeyCode::Char('[') => {
+            return ControlAction::MoveSpectrumCursor(-1);
+        }
+        KeyCode::Char(']') => {
+            return ControlAction::MoveSpectrumCursor(1);
+        }
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            return ControlAction::ZoomWaveform(1);
+        }
+        KeyCode::Char('-') | KeyCode::Char('_') => {
+            return ControlAction::ZoomWaveform(-1);
+        }
+        KeyCode::Char('t') | KeyCode::Char('T') => {
+            return ControlAction::ToggleFollow;
+        }
+        KeyCode::PageUp => {
+            return ControlAction::ScrollLyrics(-LYRICS_SCROLL_STEP);
+        }
+        KeyCode::PageDown => {
+            return ControlAction::ScrollLyrics(LYRICS_SCROLL_STEP);
+        }
+        _ => {}
+    }
+
+    ControlAction::Continue
+}
+
+/// Clicking or dragging on the waveform or progress bar seeks, scrolling
+/// over the volume bar changes the volume, and clicking a control hint
+/// presses its key.
+fn handle_mouse(mouse: MouseEvent, player: &mut Player, ui_state: &UIState) -> ControlAction {
+    let Some(target) = ui_state.click_target(mouse.column, mouse.row) else {
+        return ControlAction::Continue;
+    };
+
+    match (mouse.kind, target) {
+        (
+            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left),
+            ClickTarget::Seek(fraction),
+        ) => {
+            player.seek_to(player.duration().mul_f64(fraction));
+        }
+        (MouseEventKind::ScrollUp, ClickTarget::Volume) => {
+            player.set_volume((player.volume() + player.volume_step).min(1.0));
+        }
+        (MouseEventKind::ScrollDown, ClickTarget::Volume) => {
+            player.set_volume((player.volume() - player.volume_step).max(0.0));
+        }
+        (MouseEventKind::Down(MouseButton::Left), ClickTarget::Key(key)) => {
+            return handle_key(KeyCode::Char(key), player);
+        }
+        (
+            MouseEventKind::Moved | MouseEventKind::Down(MouseButton::Left),
+            ClickTarget::Spectrum(column),
+        ) => {
+            return ControlAction::SetSpectrumCursor(column);
         }
+        _ => {}
     }
 
-    Ok(ControlAction::Continue)
+    ControlAction::Continue
 }
@@ -0,0 +1,374 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use crossterm::{cursor::MoveTo, queue};
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+use ratatui::{Frame, layout::Rect, style::Color};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Image files looked for next to the audio when nothing is embedded.
+const SIDECAR_NAMES: &[&str] = &["cover", "folder", "front", "album"];
+const SIDECAR_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+
+/// Used when the terminal doesn't report its size in pixels.
+const FALLBACK_CELL_SIZE: (u32, u32) = (10, 20);
+
+/// Fixed id for the kitty protocol, so each cover replaces the last one.
+const KITTY_IMAGE_ID: u32 = 0xa92;
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphicsProtocol {
+    Auto,
+    Kitty,
+    Sixel,
+    HalfBlocks,
+    Off,
+}
+
+impl GraphicsProtocol {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" => Some(GraphicsProtocol::Auto),
+            "kitty" => Some(GraphicsProtocol::Kitty),
+            "sixel" => Some(GraphicsProtocol::Sixel),
+            "blocks" => Some(GraphicsProtocol::HalfBlocks),
+            "off" => Some(GraphicsProtocol::Off),
+            _ => None,
+        }
+    }
+
+    /// Replaces `Auto` with whatever the terminal looks like it supports.
+    pub fn resolve(self) -> Self {
+        if self != GraphicsProtocol::Auto {
+            return self;
+        }
+
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let program = var("TERM_PROGRAM");
+
+        // Multiplexers only pass graphics through when wrapped in their own
+        // escape sequences, so stick to plain text there.
+        if std::env::var_os("TMUX").is_some() || term.starts_with("screen") {
+            GraphicsProtocol::HalfBlocks
+        } else if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || term == "xterm-ghostty"
+            || program == "ghostty"
+            || program == "WezTerm"
+        {
+            GraphicsProtocol::Kitty
+        } else if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || program == "iTerm.app"
+        {
+            GraphicsProtocol::Sixel
+        } else {
+            GraphicsProtocol::HalfBlocks
+        }
+    }
+}
+
+/// Reads `cover.jpg`, `folder.png` and similar from the audio file's
+/// directory, ignoring case.
+pub fn find_sidecar(audio: &Path) -> Option<Arc<[u8]>> {
+    let dir = audio.parent().filter(|p| !p.as_os_str().is_empty());
+    let mut candidates: Vec<_> = fs::read_dir(dir.unwrap_or(Path::new(".")))
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name_matches = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| SIDECAR_NAMES.iter().any(|n| s.eq_ignore_ascii_case(n)));
+            let ext_matches = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| SIDECAR_EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)));
+            name_matches && ext_matches
+        })
+        .collect();
+    candidates.sort();
+
+    candidates
+        .into_iter()
+        .find_map(|path| fs::read(path).ok())
+        .map(Arc::from)
+}
+
+/// The current track's cover, decoded once and rescaled only when the space
+/// it is drawn in changes.
+pub struct CoverArt {
+    protocol: GraphicsProtocol,
+    // The encoded bytes `image` came from, so cue tracks sharing a file don't
+    // decode the same picture again.
+    source: Option<Arc<[u8]>>,
+    image: Option<RgbImage>,
+    // Half blocks: the image scaled to fit the area it was last drawn in.
+    scaled: Option<(Rect, RgbImage)>,
+    // Kitty and sixel: escape sequences for drawing at a given cell
+    // rectangle, the rectangle wanted by the last `render` and what is on
+    // screen, along with the terminal size it was drawn at.
+    encoded: Option<(Rect, Vec<u8>)>,
+    wanted: Option<Rect>,
+    placed: Option<(Rect, Rect)>,
+}
+
+impl CoverArt {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        Self {
+            protocol: protocol.resolve(),
+            source: None,
+            image: None,
+            scaled: None,
+            encoded: None,
+            wanted: None,
+            placed: None,
+        }
+    }
+
+    pub fn set_source(&mut self, source: Option<&Arc<[u8]>>) {
+        let unchanged = match (&self.source, source) {
+            (Some(current), Some(new)) => Arc::ptr_eq(current, new) || current == new,
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged || self.protocol == GraphicsProtocol::Off {
+            return;
+        }
+
+        self.source = source.cloned();
+        self.image =
+            source.and_then(|bytes| image::load_from_memory(bytes).ok().map(|i| i.to_rgb8()));
+        self.scaled = None;
+        self.encoded = None;
+        // Forces the new picture out even if it lands in the same place.
+        self.placed = None;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.protocol != GraphicsProtocol::Off && self.image.is_some()
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(image) = &self.image else {
+            return;
+        };
+        if area.is_empty() {
+            return;
+        }
+
+        match self.protocol {
+            GraphicsProtocol::HalfBlocks => {
+                if self.scaled.as_ref().is_none_or(|(rect, _)| *rect != area) {
+                    let (width, height) = fit(image, area.width as u32, area.height as u32 * 2);
+                    let scaled = imageops::resize(image, width, height, FilterType::Triangle);
+                    self.scaled = Some((area, scaled));
+                }
+                if let Some((_, scaled)) = &self.scaled {
+                    draw_half_blocks(frame, area, scaled);
+                }
+            }
+            GraphicsProtocol::Kitty | GraphicsProtocol::Sixel => {
+                let (cell_width, cell_height) = cell_size();
+                let (width, height) = fit(
+                    image,
+                    area.width as u32 * cell_width,
+                    area.height as u32 * cell_height,
+                );
+                let columns = width.div_ceil(cell_width).min(area.width as u32) as u16;
+                let rows = height.div_ceil(cell_height).min(area.height as u32) as u16;
+                let target = Rect::new(
+                    area.x + (area.width - columns) / 2,
+                    area.y + (area.height - rows) / 2,
+                    columns,
+                    rows,
+                );
+
+                if self
+                    .encoded
+                    .as_ref()
+                    .is_none_or(|(rect, _)| *rect != target)
+                {
+                    let scaled = imageops::resize(image, width, height, FilterType::Triangle);
+                    let data = if self.protocol == GraphicsProtocol::Kitty {
+                        encode_kitty(&scaled)
+                    } else {
+                        encode_sixel(&scaled)
+                    };
+                    self.encoded = Some((target, data));
+                }
+
+                // Leave these cells alone so drawing the rest of the UI
+                // doesn't paint over the picture.
+                let buffer = frame.buffer_mut();
+                for y in target.top()..target.bottom() {
+                    for x in target.left()..target.right() {
+                        buffer[(x, y)].set_skip(true);
+                    }
+                }
+                self.wanted = Some(target);
+            }
+            GraphicsProtocol::Auto | GraphicsProtocol::Off => {}
+        }
+    }
+
+    /// Sends the picture to the terminal after a frame has been drawn, if it
+    /// isn't already there. Only does anything for kitty and sixel.
+    pub fn flush<W: Write>(&mut self, out: &mut W, screen: Rect) -> io::Result<()> {
+        let wanted = self.wanted.take().map(|rect| (screen, rect));
+        if wanted == self.placed {
+            return Ok(());
+        }
+
+        // Sixel pixels go away once the cells under them are redrawn, which
+        // happens as soon as they stop being skipped; kitty keeps images on
+        // their own layer until told to delete them.
+        if self.protocol == GraphicsProtocol::Kitty && self.placed.is_some() {
+            write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", KITTY_IMAGE_ID)?;
+        }
+
+        if let Some((_, target)) = wanted
+            && let Some((rect, data)) = &self.encoded
+            && *rect == target
+        {
+            queue!(out, MoveTo(target.x, target.y))?;
+            out.write_all(data)?;
+        }
+
+        self.placed = wanted;
+        out.flush()
+    }
+
+    /// Removes a kitty image before leaving the alternate screen.
+    pub fn clear<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.protocol == GraphicsProtocol::Kitty && self.placed.take().is_some() {
+            write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", KITTY_IMAGE_ID)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Largest size with the image's aspect ratio that fits in `width` × `height`.
+fn fit(image: &RgbImage, width: u32, height: u32) -> (u32, u32) {
+    let (image_width, image_height) = image.dimensions();
+    let scale = (width as f64 / image_width as f64).min(height as f64 / image_height as f64);
+    (
+        ((image_width as f64 * scale) as u32).clamp(1, width.max(1)),
+        ((image_height as f64 * scale) as u32).clamp(1, height.max(1)),
+    )
+}
+
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns).max(1) as u32,
+            (size.height / size.rows).max(1) as u32,
+        ),
+        _ => FALLBACK_CELL_SIZE,
+    }
+}
+
+/// Two pixels per cell: the upper one as the `▀` glyph, the lower one as the
+/// background.
+fn draw_half_blocks(frame: &mut Frame, area: Rect, image: &RgbImage) {
+    let (width, height) = image.dimensions();
+    let rows = height.div_ceil(2) as u16;
+    let x_offset = (area.width - width as u16) / 2;
+    let y_offset = (area.height - rows) / 2;
+    let color = |pixel: &Rgb<u8>| Color::Rgb(pixel[0], pixel[1], pixel[2]);
+
+    let buffer = frame.buffer_mut();
+    for row in 0..rows {
+        for x in 0..width {
+            let y = row as u32 * 2;
+            let cell = &mut buffer[(area.x + x_offset + x as u16, area.y + y_offset + row)];
+            cell.set_symbol("▀");
+            cell.set_fg(color(image.get_pixel(x, y)));
+            if y + 1 < height {
+                cell.set_bg(color(image.get_pixel(x, y + 1)));
+            }
+        }
+    }
+}
+
+fn encode_kitty(image: &RgbImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let data = BASE64.encode(image.as_raw());
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+    let mut out = Vec::with_capacity(data.len() + chunks.len() * 64);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            // Raw RGB, quiet, and leave the cursor where it is.
+            write!(
+                out,
+                "\x1b_Ga=T,f=24,s={},v={},i={},q=2,C=1,m={};",
+                width, height, KITTY_IMAGE_ID, more
+            )
+            .ok();
+        } else {
+            write!(out, "\x1b_Gm={};", more).ok();
+        }
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    out
+}
+
+/// Encodes `image` against a 6×6×6 colour cube, which is plenty at the sizes
+/// a cover is shown at and avoids a palette search.
+fn encode_sixel(image: &RgbImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let level = |c: u8| (c as usize * 5 + 127) / 255;
+    let palette_index = |p: &Rgb<u8>| level(p[0]) * 36 + level(p[1]) * 6 + level(p[2]);
+
+    // Transparent background, 1:1 pixel aspect.
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for i in 0..216 {
+        let (r, g, b) = (i / 36, i / 6 % 6, i % 6);
+        write!(out, "#{};2;{};{};{}", i, r * 20, g * 20, b * 20).ok();
+    }
+
+    for band in (0..height).step_by(6) {
+        // One row of sixels per colour used in this band of six pixel rows.
+        let mut layers: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for x in 0..width {
+            for dy in 0..(height - band).min(6) {
+                let index = palette_index(image.get_pixel(x, band + dy));
+                layers
+                    .entry(index)
+                    .or_insert_with(|| vec![0; width as usize])[x as usize] |= 1 << dy;
+            }
+        }
+
+        for (index, bits) in layers {
+            write!(out, "#{}", index).ok();
+            let mut x = 0;
+            while x < bits.len() {
+                let run = bits[x..].iter().take_while(|&&b| b == bits[x]).count();
+                let symbol = (63 + bits[x]) as char;
+                if run > 3 {
+                    write!(out, "!{}{}", run, symbol).ok();
+                } else {
+                    out.extend(std::iter::repeat_n(symbol, run));
+                }
+                x += run;
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out.into_bytes()
+}
@@ -0,0 +1,271 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::playlist::{Track, is_supported};
+use crate::text_file;
+
+/// CUE timestamps are `mm:ss:ff` with 75 frames per second (CD sectors).
+const FRAMES_PER_SECOND: u64 = 75;
+
+pub fn is_cue_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cue"))
+}
+
+/// Looks for `song.cue` or `song.flac.cue` next to `audio`.
+pub fn find_sheet(audio: &Path) -> Option<PathBuf> {
+    let mut with_suffix = audio.as_os_str().to_owned();
+    with_suffix.push(".cue");
+
+    [audio.with_extension("cue"), PathBuf::from(with_suffix)]
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+}
+
+/// Reads a cue sheet and returns one virtual track per `TRACK` entry.
+///
+/// When `audio` is given the sheet was found next to that file, and a sheet
+/// describing a single `FILE` is applied to it even if the names differ, as
+/// rips are often re-encoded after the sheet was written.
+pub fn load(path: &Path, audio: Option<&Path>) -> io::Result<Vec<Track>> {
+    let text = text_file::read(path)?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let sheet = parse(&text);
+
+    let single_file = sheet.files.len() == 1;
+    let mut tracks = Vec::new();
+
+    for file in sheet.files {
+        let file_path = match audio {
+            Some(audio) if single_file => audio.to_path_buf(),
+            _ => match locate(base, &file.name) {
+                Some(file_path) => file_path,
+                None => {
+                    eprintln!(
+                        "Warning: skipping {}: file not found",
+                        base.join(&file.name).display()
+                    );
+                    continue;
+                }
+            },
+        };
+
+        let starts: Vec<Duration> = file.tracks.iter().map(|t| t.start).collect();
+        for (i, entry) in file.tracks.into_iter().enumerate() {
+            tracks.push(Track {
+                path: file_path.clone(),
+                title: entry.title,
+                artist: entry.performer.or_else(|| sheet.performer.clone()),
+                duration: None,
+                start: entry.start,
+                end: starts.get(i + 1).copied(),
+            });
+        }
+    }
+
+    Ok(tracks)
+}
+
+struct Sheet {
+    performer: Option<String>,
+    files: Vec<FileEntry>,
+}
+
+struct FileEntry {
+    name: String,
+    tracks: Vec<TrackEntry>,
+}
+
+struct TrackEntry {
+    title: Option<String>,
+    performer: Option<String>,
+    start: Duration,
+}
+
+fn parse(text: &str) -> Sheet {
+    let mut sheet = Sheet {
+        performer: None,
+        files: Vec::new(),
+    };
+    // Set while inside a `TRACK` block whose `INDEX 01` hasn't been seen yet.
+    let mut pending: Option<TrackEntry> = None;
+    let mut in_track = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                sheet.files.push(FileEntry {
+                    name: parse_file_name(rest),
+                    tracks: Vec::new(),
+                });
+                in_track = false;
+            }
+            "TRACK" => {
+                in_track = true;
+                pending = Some(TrackEntry {
+                    title: None,
+                    performer: None,
+                    start: Duration::ZERO,
+                });
+            }
+            "TITLE" if in_track => {
+                if let Some(entry) = pending.as_mut() {
+                    entry.title = Some(unquote(rest));
+                } else if let Some(entry) = current_track(&mut sheet) {
+                    entry.title = Some(unquote(rest));
+                }
+            }
+            "PERFORMER" if in_track => {
+                if let Some(entry) = pending.as_mut() {
+                    entry.performer = Some(unquote(rest));
+                } else if let Some(entry) = current_track(&mut sheet) {
+                    entry.performer = Some(unquote(rest));
+                }
+            }
+            "PERFORMER" => sheet.performer = Some(unquote(rest)),
+            "INDEX" => {
+                let mut parts = rest.split_whitespace();
+                let number = parts.next().and_then(|n| n.parse::<u32>().ok());
+                let time = parts.next().and_then(parse_time);
+
+                if number == Some(1)
+                    && let Some(time) = time
+                    && let Some(mut entry) = pending.take()
+                    && let Some(file) = sheet.files.last_mut()
+                {
+                    entry.start = time;
+                    file.tracks.push(entry);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    sheet
+}
+
+fn current_track(sheet: &mut Sheet) -> Option<&mut TrackEntry> {
+    sheet.files.last_mut()?.tracks.last_mut()
+}
+
+/// `FILE "name with spaces.flac" WAVE` → `name with spaces.flac`
+fn parse_file_name(rest: &str) -> String {
+    if let Some(quoted) = rest.strip_prefix('"')
+        && let Some(end) = quoted.find('"')
+    {
+        return quoted[..end].to_string();
+    }
+    match rest.rsplit_once(' ') {
+        Some((name, _file_type)) => name.to_string(),
+        None => rest.to_string(),
+    }
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches('"').to_string()
+}
+
+fn parse_time(value: &str) -> Option<Duration> {
+    let mut parts = value.split(':').map(|p| p.parse::<u64>().ok());
+    let minutes = parts.next()??;
+    let seconds = parts.next()??;
+    let frames = parts.next()??;
+
+    let total_frames = (minutes * 60 + seconds) * FRAMES_PER_SECOND + frames;
+    Some(Duration::from_nanos(
+        total_frames * 1_000_000_000 / FRAMES_PER_SECOND,
+    ))
+}
+
+/// Finds the file a sheet refers to, falling back to the same name with a
+/// different audio extension (sheets written for a WAV rip that was later
+/// encoded to FLAC).
+fn locate(base: &Path, name: &str) -> Option<PathBuf> {
+    let path = base.join(name);
+    if path.is_file() {
+        return Some(path);
+    }
+
+    let stem = path.file_stem()?.to_owned();
+    let dir = path.parent()?;
+    let mut candidates: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.file_stem() == Some(stem.as_os_str()) && is_supported(p))
+        .collect();
+    candidates.sort();
+    candidates.into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_start_at_index_01() {
+        let sheet = parse(
+            "REM GENRE Rock\n\
+             PERFORMER \"Album Artist\"\n\
+             TITLE \"Album\"\n\
+             FILE \"disc one.flac\" WAVE\n\
+             \x20 TRACK 01 AUDIO\n\
+             \x20   TITLE \"Intro\"\n\
+             \x20   INDEX 01 00:00:00\n\
+             \x20 TRACK 02 AUDIO\n\
+             \x20   TITLE \"Guest Spot\"\n\
+             \x20   PERFORMER \"Guest\"\n\
+             \x20   INDEX 00 02:58:00\n\
+             \x20   INDEX 01 03:00:37\n\
+             FILE disc2.wav WAVE\n\
+             \x20 TRACK 03 AUDIO\n\
+             \x20   INDEX 01 00:00:00\n",
+        );
+
+        assert_eq!(sheet.performer.as_deref(), Some("Album Artist"));
+        assert_eq!(sheet.files.len(), 2);
+
+        let first = &sheet.files[0];
+        assert_eq!(first.name, "disc one.flac");
+        assert_eq!(first.tracks.len(), 2);
+        assert_eq!(first.tracks[0].title.as_deref(), Some("Intro"));
+        assert_eq!(first.tracks[0].start, Duration::ZERO);
+        assert_eq!(first.tracks[1].performer.as_deref(), Some("Guest"));
+        assert_eq!(
+            first.tracks[1].start,
+            Duration::from_secs(180) + Duration::from_nanos(37 * 1_000_000_000 / 75)
+        );
+
+        let second = &sheet.files[1];
+        assert_eq!(second.name, "disc2.wav");
+        assert_eq!(second.tracks.len(), 1);
+        assert_eq!(second.tracks[0].title, None);
+    }
+
+    #[test]
+    fn titles_after_index_still_apply() {
+        let sheet = parse(
+            "FILE \"a.flac\" WAVE\n\
+             TRACK 01 AUDIO\n\
+             INDEX 01 00:00:00\n\
+             TITLE \"Late\"\n",
+        );
+        assert_eq!(sheet.files[0].tracks[0].title.as_deref(), Some("Late"));
+    }
+
+    #[test]
+    fn malformed_times_are_rejected() {
+        assert_eq!(
+            parse_time("01:02:03"),
+            Some(Duration::from_nanos(62_040_000_000))
+        );
+        assert_eq!(parse_time("01:02"), None);
+        assert_eq!(parse_time("aa:00:00"), None);
+    }
+}
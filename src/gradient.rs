@@ -0,0 +1,78 @@
+use ratatui::style::Color;
+
+// Evenly spaced stops along matplotlib's colour maps of the same names.
+const VIRIDIS: &[(u8, u8, u8)] = &[
+    (0x44, 0x01, 0x54),
+    (0x48, 0x28, 0x78),
+    (0x3e, 0x49, 0x89),
+    (0x31, 0x68, 0x8e),
+    (0x26, 0x82, 0x8e),
+    (0x1f, 0x9e, 0x89),
+    (0x35, 0xb7, 0x79),
+    (0x6e, 0xce, 0x58),
+    (0xb5, 0xde, 0x2b),
+    (0xfd, 0xe7, 0x25),
+];
+const MAGMA: &[(u8, u8, u8)] = &[
+    (0x00, 0x00, 0x04),
+    (0x18, 0x0f, 0x3d),
+    (0x44, 0x0f, 0x76),
+    (0x72, 0x1f, 0x81),
+    (0x9e, 0x2f, 0x7f),
+    (0xcd, 0x40, 0x71),
+    (0xf1, 0x60, 0x5d),
+    (0xfd, 0x96, 0x68),
+    (0xfe, 0xca, 0x8d),
+    (0xfc, 0xfd, 0xbf),
+];
+const GRAYSCALE: &[(u8, u8, u8)] = &[(0x00, 0x00, 0x00), (0xff, 0xff, 0xff)];
+
+/// Colours for levels in the spectrogram, from quiet to loud.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gradient {
+    Viridis,
+    Magma,
+    Grayscale,
+}
+
+impl Gradient {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "viridis" => Some(Gradient::Viridis),
+            "magma" => Some(Gradient::Magma),
+            "grayscale" | "greyscale" => Some(Gradient::Grayscale),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Gradient::Viridis => Gradient::Magma,
+            Gradient::Magma => Gradient::Grayscale,
+            Gradient::Grayscale => Gradient::Viridis,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Gradient::Viridis => "viridis",
+            Gradient::Magma => "magma",
+            Gradient::Grayscale => "grayscale",
+        }
+    }
+
+    /// The colour for `level`, from 0.0 to 1.0.
+    pub fn color(self, level: f32) -> Color {
+        let stops = match self {
+            Gradient::Viridis => VIRIDIS,
+            Gradient::Magma => MAGMA,
+            Gradient::Grayscale => GRAYSCALE,
+        };
+        let position = level.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let low = (position as usize).min(stops.len() - 2);
+        let t = position - low as f32;
+        let (from, to) = (stops[low], stops[low + 1]);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::Rgb(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+    }
+}
@@ -0,0 +1,523 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::text_file;
+
+#[derive(Clone)]
+pub enum Lyrics {
+    /// Lines sorted by time.
+    Synced(Vec<LyricLine>),
+    Plain(Vec<String>),
+}
+
+#[derive(Clone)]
+pub struct LyricLine {
+    pub time: Duration,
+    pub text: String,
+    /// Word-level timing from enhanced LRC (`<mm:ss.xx>`) or SYLT syllables.
+    /// Empty when only the line is timed.
+    pub words: Vec<(Duration, String)>,
+}
+
+impl Lyrics {
+    /// Parses LRC, falling back to plain text when no line has a timestamp.
+    pub fn parse(text: &str) -> Lyrics {
+        let mut offset_ms: i64 = 0;
+        let mut synced = Vec::new();
+        let mut plain = Vec::new();
+
+        for raw in text.lines() {
+            let mut rest = raw.trim();
+            let mut times = Vec::new();
+            let mut is_tag = false;
+
+            while let Some(inner) = rest.strip_prefix('[')
+                && let Some((tag, after)) = inner.split_once(']')
+            {
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time);
+                } else if let Some((key, value)) = tag.split_once(':') {
+                    // ID tags such as `[ar:Artist]`; only the offset matters here.
+                    if key.trim().eq_ignore_ascii_case("offset") {
+                        offset_ms = value.trim().trim_start_matches('+').parse().unwrap_or(0);
+                    }
+                    is_tag = true;
+                } else {
+                    break;
+                }
+                rest = after;
+            }
+
+            if times.is_empty() {
+                if !is_tag {
+                    plain.push(raw.trim_end().to_string());
+                }
+                continue;
+            }
+
+            let (text, words) = parse_words(rest);
+            for time in times {
+                synced.push(LyricLine {
+                    time,
+                    text: text.clone(),
+                    words: words.clone(),
+                });
+            }
+        }
+
+        if synced.is_empty() {
+            // Drop the blank lines around the text but keep the ones between
+            // verses.
+            let start = plain
+                .iter()
+                .position(|l| !l.is_empty())
+                .unwrap_or(plain.len());
+            let end = plain
+                .iter()
+                .rposition(|l| !l.is_empty())
+                .map_or(start, |i| i + 1);
+            return Lyrics::Plain(plain[start..end].to_vec());
+        }
+
+        // A positive offset makes the lyrics come up sooner.
+        let shift = |time: Duration| {
+            let ms = time.as_millis() as i64 - offset_ms;
+            Duration::from_millis(ms.max(0) as u64)
+        };
+        for line in &mut synced {
+            line.time = shift(line.time);
+            for (time, _) in &mut line.words {
+                *time = shift(*time);
+            }
+        }
+        synced.sort_by_key(|line| line.time);
+
+        Lyrics::Synced(synced)
+    }
+
+    /// The part of whole-file lyrics that falls within `start..end`, with
+    /// times made relative to `start`, for tracks split out by a cue sheet.
+    pub fn slice(&self, start: Duration, end: Option<Duration>) -> Option<Lyrics> {
+        let Lyrics::Synced(lines) = self else {
+            return Some(self.clone());
+        };
+
+        let lines: Vec<LyricLine> = lines
+            .iter()
+            .filter(|line| line.time >= start && end.is_none_or(|end| line.time < end))
+            .map(|line| LyricLine {
+                time: line.time - start,
+                text: line.text.clone(),
+                words: line
+                    .words
+                    .iter()
+                    .map(|(time, word)| (time.saturating_sub(start), word.clone()))
+                    .collect(),
+            })
+            .collect();
+
+        (!lines.is_empty()).then_some(Lyrics::Synced(lines))
+    }
+}
+
+/// Lyrics that live outside the regular tags: `song.lrc` next to the file,
+/// or an ID3 `SYLT` frame.
+pub fn load(audio: &Path) -> Option<Lyrics> {
+    let sidecar = ["lrc", "LRC"]
+        .iter()
+        .map(|ext| audio.with_extension(ext))
+        .find(|path| path.is_file());
+
+    if let Some(path) = sidecar
+        && let Ok(text) = text_file::read(&path)
+    {
+        return Some(Lyrics::parse(&text));
+    }
+
+    read_sylt(audio)
+}
+
+/// `mm:ss.xx`, `mm:ss:xx` or `mm:ss`.
+fn parse_timestamp(value: &str) -> Option<Duration> {
+    let (minutes, seconds) = value.trim().split_once(':')?;
+    let minutes: u64 = minutes.parse().ok()?;
+    // Some editors write `mm:ss:xx` for hundredths.
+    let seconds = seconds.replacen(':', ".", 1);
+    let seconds: f64 = seconds.parse().ok()?;
+    if !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+/// Splits enhanced LRC text into its words, returning the text without the
+/// `<mm:ss.xx>` markers.
+fn parse_words(text: &str) -> (String, Vec<(Duration, String)>) {
+    let mut plain = String::new();
+    let mut words: Vec<(Duration, String)> = Vec::new();
+    let mut rest = text;
+
+    while let Some(open) = rest.find('<') {
+        let Some(close) = rest[open..].find('>').map(|i| open + i) else {
+            break;
+        };
+        let Some(time) = parse_timestamp(&rest[open + 1..close]) else {
+            plain.push_str(&rest[..=close]);
+            rest = &rest[close + 1..];
+            continue;
+        };
+
+        plain.push_str(&rest[..open]);
+        if let Some((_, word)) = words.last_mut() {
+            word.push_str(&rest[..open]);
+        }
+        words.push((time, String::new()));
+        rest = &rest[close + 1..];
+    }
+
+    plain.push_str(rest);
+    if let Some((_, word)) = words.last_mut() {
+        word.push_str(rest);
+    }
+    // A trailing marker only says when the last word ends.
+    words.retain(|(_, word)| !word.is_empty());
+
+    (plain.trim().to_string(), words)
+}
+
+/// Reads the first `SYLT` frame with millisecond timestamps from an ID3v2
+/// tag at the start of the file. Symphonia skips these frames.
+fn read_sylt(path: &Path) -> Option<Lyrics> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut header = [0; 10];
+    file.read_exact(&mut header).ok()?;
+    if &header[..3] != b"ID3" {
+        return None;
+    }
+
+    let version = header[3];
+    let flags = header[5];
+    let size = syncsafe(&header[6..10]) as usize;
+    let mut tag = vec![0; size];
+    file.read_exact(&mut tag).ok()?;
+
+    if flags & 0x80 != 0 && version < 4 {
+        tag = remove_unsynchronisation(&tag);
+    }
+
+    let mut pos = 0;
+    if flags & 0x40 != 0 {
+        pos = match version {
+            3 => 4 + u32::from_be_bytes(tag.get(..4)?.try_into().ok()?) as usize,
+            4 => syncsafe(tag.get(..4)?) as usize,
+            _ => 0,
+        };
+    }
+
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+
+    while pos + header_len <= tag.len() {
+        let id = &tag[pos..pos + id_len];
+        if id[0] == 0 {
+            break;
+        }
+
+        let frame_size = match version {
+            2 => u32::from_be_bytes([0, tag[pos + 3], tag[pos + 4], tag[pos + 5]]) as usize,
+            3 => u32::from_be_bytes(tag[pos + 4..pos + 8].try_into().ok()?) as usize,
+            _ => syncsafe(&tag[pos + 4..pos + 8]) as usize,
+        };
+        let body_start = pos + header_len;
+        let body = tag.get(body_start..body_start + frame_size)?;
+
+        if id == b"SYLT" || id == b"SLT" {
+            let mut body = body.to_vec();
+            if version == 4 {
+                let format_flags = tag[pos + 9];
+                if format_flags & 0x02 != 0 {
+                    body = remove_unsynchronisation(&body);
+                }
+                if format_flags & 0x01 != 0 {
+                    body.drain(..4.min(body.len()));
+                }
+            }
+            if let Some(lyrics) = parse_sylt(&body) {
+                return Some(lyrics);
+            }
+        }
+
+        pos = body_start + frame_size;
+    }
+
+    None
+}
+
+fn parse_sylt(body: &[u8]) -> Option<Lyrics> {
+    let encoding = *body.first()?;
+    let time_format = *body.get(4)?;
+    // 1 is MPEG frames, which would need the stream's frame length.
+    if time_format != 2 {
+        return None;
+    }
+
+    let mut rest = body.get(6..)?;
+    // Content descriptor.
+    let (_, after) = read_terminated(rest, encoding)?;
+    rest = after;
+
+    let mut syllables = Vec::new();
+    while !rest.is_empty() {
+        let (text, after) = read_terminated(rest, encoding)?;
+        let time = u32::from_be_bytes(after.get(..4)?.try_into().ok()?);
+        syllables.push((Duration::from_millis(time as u64), text));
+        rest = &after[4..];
+    }
+
+    // Either one entry per line, or syllables where a leading newline starts
+    // the next line.
+    let split_syllables = syllables
+        .iter()
+        .skip(1)
+        .any(|(_, text)| text.starts_with(['\n', '\r']));
+
+    let mut lines: Vec<LyricLine> = Vec::new();
+    for (time, text) in syllables {
+        let starts_line = text.starts_with(['\n', '\r']) || !split_syllables;
+        let text = text.trim_start_matches(['\n', '\r']).to_string();
+
+        match lines.last_mut() {
+            Some(line) if !starts_line => {
+                line.text.push_str(&text);
+                line.words.push((time, text));
+            }
+            _ => lines.push(LyricLine {
+                time,
+                text: text.clone(),
+                words: if split_syllables {
+                    vec![(time, text)]
+                } else {
+                    Vec::new()
+                },
+            }),
+        }
+    }
+
+    for line in &mut lines {
+        line.text = line.text.trim().to_string();
+    }
+    lines.sort_by_key(|line| line.time);
+
+    (!lines.is_empty()).then_some(Lyrics::Synced(lines))
+}
+
+/// Reads a string terminated by a null in the given ID3 text encoding and
+/// returns it along with what follows the terminator.
+fn read_terminated(data: &[u8], encoding: u8) -> Option<(String, &[u8])> {
+    match encoding {
+        // Latin-1 and UTF-8 end with a single null.
+        0 | 3 => {
+            let end = data.iter().position(|&b| b == 0)?;
+            let bytes = &data[..end];
+            let text = if encoding == 3 {
+                String::from_utf8_lossy(bytes).into_owned()
+            } else {
+                bytes.iter().map(|&b| b as char).collect()
+            };
+            Some((text, &data[end + 1..]))
+        }
+        // UTF-16 with a BOM, or big-endian without one, end with two nulls.
+        1 | 2 => {
+            let end = data
+                .chunks_exact(2)
+                .position(|pair| pair == [0, 0])
+                .map(|i| i * 2)?;
+            let mut bytes = &data[..end];
+            let mut big_endian = encoding == 2;
+            if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+                big_endian = false;
+                bytes = rest;
+            } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+                big_endian = true;
+                bytes = rest;
+            }
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| {
+                    if big_endian {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    }
+                })
+                .collect();
+            Some((String::from_utf16_lossy(&units), &data[end + 2..]))
+        }
+        _ => None,
+    }
+}
+
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |acc, &b| (acc << 7) | (b & 0x7F) as u32)
+}
+
+/// Undoes ID3 unsynchronisation, which inserts a zero after every 0xFF.
+fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        out.push(data[i]);
+        if data[i] == 0xFF && data.get(i + 1) == Some(&0) {
+            i += 1;
+        }
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn synced(lyrics: Lyrics) -> Vec<LyricLine> {
+        match lyrics {
+            Lyrics::Synced(lines) => lines,
+            Lyrics::Plain(_) => panic!("expected synced lyrics"),
+        }
+    }
+
+    #[test]
+    fn lrc_lines_are_sorted_and_offset() {
+        let lines = synced(Lyrics::parse(
+            "[ar:Someone]\n\
+             [offset:+500]\n\
+             [00:12.00][01:02.50]Chorus\n\
+             [00:05.25]Verse\n\
+             [00:10:00]Hundredths after a colon\n",
+        ));
+
+        let times: Vec<Duration> = lines.iter().map(|line| line.time).collect();
+        assert_eq!(times, [ms(4750), ms(9500), ms(11500), ms(62000)]);
+        assert_eq!(lines[0].text, "Verse");
+        assert_eq!(lines[2].text, "Chorus");
+        assert_eq!(lines[3].text, "Chorus");
+    }
+
+    #[test]
+    fn enhanced_lrc_words_are_timed() {
+        let lines = synced(Lyrics::parse(
+            "[00:01.00]<00:01.00>Hello <00:01.50>there<00:02.00>",
+        ));
+
+        assert_eq!(lines[0].text, "Hello there");
+        assert_eq!(
+            lines[0].words,
+            [
+                (ms(1000), "Hello ".to_string()),
+                (ms(1500), "there".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn untimed_text_is_plain() {
+        let Lyrics::Plain(lines) = Lyrics::parse("\n[ti:Song]\nFirst\n\nSecond\n\n") else {
+            panic!("expected plain lyrics");
+        };
+        assert_eq!(lines, ["First", "", "Second"]);
+    }
+
+    #[test]
+    fn slices_keep_the_track_and_rebase_times() {
+        let lyrics = Lyrics::parse("[00:01.00]a\n[00:05.00]b\n[00:09.00]c\n");
+        let lines = synced(lyrics.slice(ms(4000), Some(ms(9000))).unwrap());
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "b");
+        assert_eq!(lines[0].time, ms(1000));
+        assert!(lyrics.slice(ms(20000), None).is_none());
+    }
+
+    /// A SYLT body in Latin-1 with millisecond timestamps.
+    fn sylt_body(entries: &[(&str, u32)]) -> Vec<u8> {
+        let mut body = vec![0];
+        body.extend_from_slice(b"eng");
+        body.extend_from_slice(&[2, 1, 0]);
+        for (text, time) in entries {
+            body.extend_from_slice(text.as_bytes());
+            body.push(0);
+            body.extend_from_slice(&time.to_be_bytes());
+        }
+        body
+    }
+
+    #[test]
+    fn sylt_syllables_join_into_lines() {
+        let body = sylt_body(&[("Hel", 1000), ("lo", 1200), ("\nWorld", 3000)]);
+        let lines = synced(parse_sylt(&body).unwrap());
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text, "Hello");
+        assert_eq!(lines[0].words.len(), 2);
+        assert_eq!(lines[1].time, ms(3000));
+        assert_eq!(lines[1].text, "World");
+    }
+
+    #[test]
+    fn sylt_in_mpeg_frames_is_ignored() {
+        let mut body = sylt_body(&[("Line", 1000)]);
+        body[4] = 1;
+        assert!(parse_sylt(&body).is_none());
+    }
+
+    #[test]
+    fn sylt_is_found_in_an_id3v23_tag() {
+        let body = sylt_body(&[("One", 500), ("Two", 1500)]);
+        let mut frame = b"SYLT".to_vec();
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&body);
+
+        let size = frame.len() as u32;
+        let mut file = b"ID3\x03\x00\x00".to_vec();
+        file.extend((0..4).rev().map(|i| ((size >> (i * 7)) & 0x7F) as u8));
+        file.extend_from_slice(&frame);
+        file.extend_from_slice(b"audio");
+
+        let path = std::env::temp_dir().join(format!("apz-sylt-test-{}.mp3", std::process::id()));
+        fs::write(&path, &file).unwrap();
+        let lyrics = read_sylt(&path);
+        fs::remove_file(&path).unwrap();
+
+        let lines = synced(lyrics.unwrap());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].text, "Two");
+        assert_eq!(lines[1].time, ms(1500));
+    }
+
+    #[test]
+    fn utf16_strings_honour_the_bom() {
+        let data = [0xFF, 0xFE, b'h', 0, b'i', 0, 0, 0, 9];
+        let (text, rest) = read_terminated(&data, 1).unwrap();
+        assert_eq!(text, "hi");
+        assert_eq!(rest, [9]);
+
+        let data = [0, b'h', 0, b'i', 0, 0];
+        assert_eq!(read_terminated(&data, 2).unwrap().0, "hi");
+    }
+
+    #[test]
+    fn unsynchronisation_drops_the_inserted_zeros() {
+        assert_eq!(
+            remove_unsynchronisation(&[0xFF, 0x00, 0xE0, 0xFF, 0x01]),
+            [0xFF, 0xE0, 0xFF, 0x01]
+        );
+    }
+}
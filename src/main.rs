@@ -1,37 +1,107 @@
 mod config;
 mod controls;
+mod cover_art;
+mod cue;
+mod gradient;
+mod lyrics;
+mod metadata;
+mod peak_cache;
 mod player;
+mod playlist;
+mod playlist_file;
+mod sample_ring;
 mod spectrum;
 mod tee_source;
+mod text_file;
+mod track_source;
 mod ui;
 mod waveform;
 
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
 use std::io;
+use std::path::Path;
 use std::process;
 
 use crate::config::Config;
 use crate::controls::{ControlAction, handle_input};
+use crate::peak_cache::PeakCache;
 use crate::player::Player;
-use crate::ui::UIState;
+use crate::playlist::Playlist;
+use crate::spectrum::SpectrumSettings;
+use crate::ui::{UIState, Visualizer};
+
+/// Where `W` writes the queue when `--save-playlist` isn't given.
+const DEFAULT_PLAYLIST_PATH: &str = "queue.m3u8";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::from_args();
 
-    let spectrum_config = if config.use_visualizer {
-        Some((config.num_bars, config.smoothing, config.bass_boost))
-    } else {
+    if config.clear_cache {
+        match peak_cache::clear() {
+            Ok((dir, removed)) => {
+                println!(
+                    "Removed {} cached waveforms from {}",
+                    removed,
+                    dir.display()
+                );
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Failed to clear the waveform cache: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let spectrum_settings = SpectrumSettings {
+        num_bars: config.num_bars,
+        attack_ms: config.attack_ms,
+        decay_ms: config.decay_ms,
+        peak_hold_ms: config.peak_hold_ms,
+        peak_fall: config.peak_fall,
+        bass_boost: config.bass_boost,
+        fft_size: config.fft_size,
+        window: config.window,
+        min_freq: config.min_freq,
+        max_freq: config.max_freq,
+        db_floor: config.db_floor,
+        db_ceiling: config.db_ceiling,
+    };
+
+    let mut playlist = Playlist::from_paths(&config.audio_paths).unwrap_or_else(|e| {
+        eprintln!("Failed to read audio files: {}", e);
+        process::exit(1);
+    });
+
+    if playlist.is_empty() {
+        eprintln!("No supported audio files found");
+        process::exit(1);
+    }
+
+    playlist.set_shuffle(config.shuffle, None);
+    playlist.set_repeat(config.repeat);
+
+    if let Some(path) = &config.save_playlist
+        && let Err(e) = playlist.save(path)
+    {
+        eprintln!("Warning: failed to save playlist to {}: {}", path, e);
+    }
+
+    let peak_cache = if config.no_cache {
         None
+    } else {
+        PeakCache::open()
     };
 
-    let player = Player::new(
-        &config.audio_path,
-        false,
-        spectrum_config,
+    let mut player = Player::new(
+        playlist,
+        peak_cache,
+        spectrum_settings,
         config.volume_step,
         config.seek_step,
     )
@@ -42,19 +112,48 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let duration = player.duration();
     let waveform = player.waveform().clone();
-    let spectrum = player.spectrum();
-    let mut ui_state = UIState::new(&config.audio_path, duration, waveform, spectrum);
+    let spectrum = (config.visualizer != Visualizer::Waveform).then(|| player.spectrum());
+    let mut ui_state = UIState::new(
+        player.current_title(),
+        player.metadata().clone(),
+        duration,
+        waveform,
+        config.waveform,
+        spectrum,
+        config.cover,
+    );
+    ui_state.visualizer = config.visualizer;
+    ui_state.spectrum_style = config.spectrum_style;
+    ui_state.show_axes = !config.no_axes;
+    ui_state.gradient = config.gradient;
+    ui_state.mid_side = config.mid_side;
+    ui_state.track_index = player.track_index();
+    ui_state.track_count = player.track_count();
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let result = run_event_loop(&mut terminal, &player, &mut ui_state);
+    let save_path = config
+        .save_playlist
+        .as_deref()
+        .unwrap_or(DEFAULT_PLAYLIST_PATH);
+    let result = run_event_loop(
+        &mut terminal,
+        &mut player,
+        &mut ui_state,
+        Path::new(save_path),
+    );
 
+    ui_state.cover.clear(terminal.backend_mut())?;
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    execute!(
+        terminal.backend_mut(),
+        DisableMouseCapture,
+        LeaveAlternateScreen
+    )?;
     terminal.show_cursor()?;
 
     result
@@ -62,18 +161,69 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 fn run_event_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    player: &Player,
+    player: &mut Player,
     ui_state: &mut UIState,
+    save_path: &Path,
 ) -> Result<(), Box<dyn std::error::Error>> {
     loop {
+        player.update();
+
+        if ui_state.track_index != player.track_index() {
+            ui_state.track_index = player.track_index();
+            ui_state.set_track(
+                player.current_title(),
+                player.metadata().clone(),
+                player.duration(),
+                player.waveform().clone(),
+            );
+        }
+
+        if player.take_waveform_update() {
+            ui_state.waveform = player.waveform().clone();
+        }
+
+        ui_state.track_position = player.track_position();
         ui_state.position = player.position();
         ui_state.volume = player.volume();
         ui_state.state = player.state();
+        ui_state.shuffle = player.shuffle();
+        ui_state.repeat = player.repeat();
 
-        terminal.draw(|f| ui::render(f, ui_state))?;
+        let frame = terminal.draw(|f| ui::render(f, ui_state))?;
+        let screen = frame.area;
+        ui_state.cover.flush(terminal.backend_mut(), screen)?;
 
-        match handle_input(player)? {
+        match handle_input(player, ui_state)? {
             ControlAction::Quit => break,
+            ControlAction::ToggleInfo => ui_state.show_info = !ui_state.show_info,
+            ControlAction::ToggleLyrics => ui_state.show_lyrics = !ui_state.show_lyrics,
+            ControlAction::ToggleStyle => {
+                if ui_state.visualizer == Visualizer::Spectrum {
+                    ui_state.spectrum_style = ui_state.spectrum_style.toggle();
+                } else {
+                    ui_state.waveform_style = ui_state.waveform_style.toggle();
+                }
+            }
+            ControlAction::CycleVisualizer => {
+                ui_state.visualizer = ui_state.visualizer.next();
+                if ui_state.visualizer != Visualizer::Waveform && ui_state.spectrum.is_none() {
+                    ui_state.spectrum = Some(player.spectrum());
+                }
+            }
+            ControlAction::ToggleFullscreen => ui_state.fullscreen = !ui_state.fullscreen,
+            ControlAction::CycleGradient => ui_state.gradient = ui_state.gradient.next(),
+            ControlAction::ToggleMidSide => ui_state.mid_side = !ui_state.mid_side,
+            ControlAction::ToggleAxes => ui_state.show_axes = !ui_state.show_axes,
+            ControlAction::MoveSpectrumCursor(columns) => ui_state.move_spectrum_cursor(columns),
+            ControlAction::SetSpectrumCursor(column) => ui_state.set_spectrum_cursor(column),
+            ControlAction::ToggleChannels => ui_state.split_channels = !ui_state.split_channels,
+            ControlAction::ZoomWaveform(steps) => ui_state.zoom_waveform(steps),
+            ControlAction::ToggleFollow => ui_state.follow_playhead = !ui_state.follow_playhead,
+            ControlAction::ScrollLyrics(lines) => ui_state.scroll_lyrics(lines),
+            ControlAction::SavePlaylist => match player.save_playlist(save_path) {
+                Ok(()) => ui_state.set_message(format!("Saved {}", save_path.display())),
+                Err(e) => ui_state.set_message(format!("Save failed: {}", e)),
+            },
             ControlAction::Continue => {}
         }
 
@@ -0,0 +1,188 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey};
+use symphonia::core::probe::Hint;
+
+use crate::lyrics::Lyrics;
+use crate::playlist::Track;
+
+/// Tags and stream details read from an audio file's headers.
+#[derive(Clone, Default)]
+pub struct TrackMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub year: Option<String>,
+    pub codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<usize>,
+    /// Average bitrate in kbit/s, worked out from the file size.
+    pub bitrate: Option<u32>,
+    /// Encoded cover image, embedded or from a file next to the track.
+    pub cover: Option<Arc<[u8]>>,
+    pub lyrics: Option<Lyrics>,
+}
+
+impl TrackMetadata {
+    /// Fills in what a playlist or cue sheet knows about `track`. A cue
+    /// sheet's titles win over the file's tags, which describe the whole rip;
+    /// otherwise tags win over playlist titles.
+    pub fn for_track(&self, track: &Track) -> TrackMetadata {
+        let mut metadata = self.clone();
+        if track.is_cue_part() {
+            metadata.title = track.title.clone();
+            metadata.artist = track.artist.clone().or(metadata.artist);
+            metadata.track_number = None;
+            metadata.lyrics = self
+                .lyrics
+                .as_ref()
+                .and_then(|lyrics| lyrics.slice(track.start, track.end));
+        } else {
+            metadata.title = metadata.title.or_else(|| track.title.clone());
+            metadata.artist = metadata.artist.or_else(|| track.artist.clone());
+        }
+
+        metadata
+    }
+
+    /// "FLAC · 44.1 kHz · 16-bit · stereo · 1011 kbps", skipping whatever
+    /// isn't known.
+    pub fn technical_summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(codec) = &self.codec {
+            parts.push(codec.to_uppercase());
+        }
+        if let Some(rate) = self.sample_rate {
+            parts.push(format!("{} kHz", rate as f32 / 1000.0));
+        }
+        if let Some(bits) = self.bit_depth {
+            parts.push(format!("{}-bit", bits));
+        }
+        if let Some(channels) = self.channels {
+            parts.push(match channels {
+                1 => "mono".to_string(),
+                2 => "stereo".to_string(),
+                n => format!("{} ch", n),
+            });
+        }
+        if let Some(bitrate) = self.bitrate {
+            parts.push(format!("{} kbps", bitrate));
+        }
+
+        parts.join(" · ")
+    }
+}
+
+pub fn probe<P: AsRef<Path>>(path: P) -> Result<TrackMetadata, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let mut metadata = TrackMetadata::default();
+
+    // Tags found ahead of the container (e.g. ID3v2 on an MP3) come first;
+    // the container's own tags override them.
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        apply_tags(&mut metadata, revision);
+        apply_visuals(&mut metadata, revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        apply_tags(&mut metadata, revision);
+        apply_visuals(&mut metadata, revision);
+    }
+
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+
+        metadata.codec = symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .map(|descriptor| match descriptor.short_name {
+                // "pcm_s16le" and friends; the bit depth is shown separately.
+                name if name.starts_with("pcm") => "pcm".to_string(),
+                name => name.to_string(),
+            });
+        metadata.sample_rate = params.sample_rate;
+        metadata.bit_depth = params.bits_per_sample;
+        metadata.channels = params.channels.map(|c| c.count());
+
+        let duration = params.time_base.zip(params.n_frames).map(|(base, frames)| {
+            let time = base.calc_time(frames);
+            Duration::from_secs_f64(time.seconds as f64 + time.frac)
+        });
+        metadata.bitrate = duration
+            .filter(|d| !d.is_zero())
+            .map(|d| (file_size as f64 * 8.0 / d.as_secs_f64() / 1000.0).round() as u32);
+    }
+
+    Ok(metadata)
+}
+
+fn apply_tags(metadata: &mut TrackMetadata, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let Some(key) = tag.std_key else { continue };
+        let value = tag.value.to_string().trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+
+        match key {
+            StandardTagKey::TrackTitle => metadata.title = Some(value),
+            StandardTagKey::Artist => metadata.artist = Some(value),
+            StandardTagKey::AlbumArtist if metadata.artist.is_none() => {
+                metadata.artist = Some(value)
+            }
+            StandardTagKey::Album => metadata.album = Some(value),
+            // ID3 `USLT`, Vorbis `LYRICS` or MP4 `©lyr`, which sometimes hold LRC.
+            StandardTagKey::Lyrics if metadata.lyrics.is_none() => {
+                metadata.lyrics = Some(Lyrics::parse(&value))
+            }
+            StandardTagKey::TrackNumber => {
+                // Often stored as "3/12".
+                metadata.track_number = value.split('/').next().and_then(|n| n.trim().parse().ok());
+            }
+            StandardTagKey::Date | StandardTagKey::OriginalDate if metadata.year.is_none() => {
+                let year: String = value.chars().take(4).collect();
+                if year.len() == 4 && year.chars().all(|c| c.is_ascii_digit()) {
+                    metadata.year = Some(year);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Takes the front cover from ID3 `APIC` frames, FLAC `PICTURE` blocks or
+/// MP4 `covr` atoms, or the first picture if none is marked as the front.
+fn apply_visuals(metadata: &mut TrackMetadata, revision: &MetadataRevision) {
+    let visuals = revision.visuals();
+    let front = visuals
+        .iter()
+        .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+        .or_else(|| visuals.first());
+
+    if let Some(visual) = front
+        && (metadata.cover.is_none() || visual.usage == Some(StandardVisualKey::FrontCover))
+    {
+        metadata.cover = Some(Arc::from(&*visual.data));
+    }
+}
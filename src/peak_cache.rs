@@ -0,0 +1,344 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::waveform::{Peaks, WaveformBar};
+
+const MAGIC: &[u8; 8] = b"APZPEAKS";
+/// Bumped whenever the file layout or the way peaks are computed changes, so
+/// older files are treated as missing and regenerated.
+const VERSION: u32 = 3;
+/// Min, max, RMS and correlation as f32, then a flags byte.
+const BAR_SIZE: usize = 17;
+const CLIPPED: u8 = 1;
+/// How big the cache may grow before the least recently used files go,
+/// about 16 hours of stereo audio.
+const MAX_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Waveform peaks stored on disk, one file per audio file, so the audio only
+/// has to be decoded the first time it is played.
+#[derive(Clone)]
+pub struct PeakCache {
+    dir: PathBuf,
+}
+
+impl PeakCache {
+    /// The cache under the user's cache directory, or `None` when there is no
+    /// home directory to put it in.
+    pub fn open() -> Option<Self> {
+        cache_dir().map(|dir| Self { dir })
+    }
+
+    /// Peaks previously stored for `audio`, if the file hasn't changed since
+    /// and the cache file is intact.
+    pub fn load(&self, audio: &Path, frames_per_peak: u32) -> Option<Peaks> {
+        let key = source_key(audio)?;
+        let path = self.dir.join(file_name(&key));
+        let data = fs::read(&path).ok()?;
+
+        let (body, checksum) = data.split_at_checked(data.len().checked_sub(8)?)?;
+        if fnv1a(body) != u64::from_le_bytes(checksum.try_into().ok()?) {
+            return None;
+        }
+
+        let mut reader = Reader { data: body };
+        if reader.take(MAGIC.len())? != MAGIC
+            || reader.u32()? != VERSION
+            || reader.u32()? != frames_per_peak
+        {
+            return None;
+        }
+        let key_len = reader.u32()? as usize;
+        if reader.take(key_len)? != key.as_slice() {
+            return None;
+        }
+
+        let count = reader.u32()? as usize;
+        let channels = reader.u32()? as usize;
+        let mut read_bars = || -> Option<Vec<WaveformBar>> {
+            let bytes = reader.take(count.checked_mul(BAR_SIZE)?)?;
+            Some(bytes.chunks_exact(BAR_SIZE).map(decode_bar).collect())
+        };
+        let mix = read_bars()?;
+        let channels = (0..channels)
+            .map(|_| read_bars())
+            .collect::<Option<Vec<_>>>()?;
+        if !reader.data.is_empty() {
+            return None;
+        }
+
+        // Marks the file as recently used, so it's the last to be evicted.
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            file.set_modified(SystemTime::now()).ok();
+        }
+        Some(Peaks { mix, channels })
+    }
+
+    pub fn store(&self, audio: &Path, frames_per_peak: u32, peaks: &Peaks) -> io::Result<()> {
+        let key = source_key(audio)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "can't stat audio file"))?;
+
+        let bars = peaks.mix.len() * (1 + peaks.channels.len());
+        let mut data = Vec::with_capacity(36 + key.len() + bars * BAR_SIZE);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&frames_per_peak.to_le_bytes());
+        data.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        data.extend_from_slice(&key);
+        data.extend_from_slice(&(peaks.mix.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(peaks.channels.len() as u32).to_le_bytes());
+        for bar in peaks.mix.iter().chain(peaks.channels.iter().flatten()) {
+            encode_bar(&mut data, bar);
+        }
+        data.extend_from_slice(&fnv1a(&data).to_le_bytes());
+
+        // Write under a temporary name first so another instance never reads
+        // a half-written file.
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(file_name(&key));
+        let temp = path.with_extension(format!("tmp{}", std::process::id()));
+        let written = fs::File::create(&temp)
+            .and_then(|mut file| file.write_all(&data))
+            .and_then(|()| fs::rename(&temp, &path));
+        if written.is_err() {
+            fs::remove_file(&temp).ok();
+        }
+        written?;
+
+        self.evict(MAX_CACHE_BYTES)
+    }
+
+    /// Deletes the least recently used files until the cache takes up no
+    /// more than `max_bytes`.
+    fn evict(&self, max_bytes: u64) -> io::Result<()> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            // Leaves other instances' half-written files alone.
+            if path.extension().is_none_or(|ext| ext != "peaks") {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            let used = metadata.modified().unwrap_or(UNIX_EPOCH);
+            files.push((used, metadata.len(), path));
+        }
+
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        files.sort();
+        for (_, len, path) in files {
+            if total <= max_bytes {
+                break;
+            }
+            // Another instance may have got there first.
+            fs::remove_file(&path).ok();
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+fn encode_bar(data: &mut Vec<u8>, bar: &WaveformBar) {
+    for value in [bar.min, bar.max, bar.rms, bar.correlation] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.push(if bar.clipped { CLIPPED } else { 0 });
+}
+
+fn decode_bar(bytes: &[u8]) -> WaveformBar {
+    let f32_at = |i: usize| f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+    WaveformBar {
+        min: f32_at(0),
+        max: f32_at(4),
+        rms: f32_at(8),
+        correlation: f32_at(12),
+        clipped: bytes[16] & CLIPPED != 0,
+    }
+}
+
+/// Deletes every cached waveform, returning the cache directory and how many
+/// files were removed.
+pub fn clear() -> io::Result<(PathBuf, usize)> {
+    let dir =
+        cache_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((dir, 0)),
+        Err(e) => return Err(e),
+    };
+
+    let mut removed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok((dir, removed))
+}
+
+/// `$XDG_CACHE_HOME/apz/peaks`, falling back to `~/.cache` (or
+/// `%LOCALAPPDATA%` on Windows).
+fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(base.join("apz").join("peaks"))
+}
+
+/// Identifies a version of an audio file: its absolute path, size and
+/// modification time.
+fn source_key(audio: &Path) -> Option<Vec<u8>> {
+    let path = audio.canonicalize().ok()?;
+    let metadata = fs::metadata(&path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    let mut key = path.to_string_lossy().into_owned().into_bytes();
+    key.extend_from_slice(&metadata.len().to_le_bytes());
+    key.extend_from_slice(&modified.as_secs().to_le_bytes());
+    key.extend_from_slice(&modified.subsec_nanos().to_le_bytes());
+    Some(key)
+}
+
+fn file_name(key: &[u8]) -> String {
+    format!("{:016x}.peaks", fnv1a(key))
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` its output is the same across Rust
+/// releases, which matters for names that outlive the process.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (head, rest) = self.data.split_at_checked(len)?;
+        self.data = rest;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cache in its own directory, with an audio file to key it on.
+    fn scratch(name: &str) -> (PeakCache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("apz-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let audio = dir.join("song.wav");
+        fs::write(&audio, b"not really audio").unwrap();
+        let cache = PeakCache {
+            dir: dir.join("peaks"),
+        };
+        (cache, audio)
+    }
+
+    fn bar(level: f32, clipped: bool) -> WaveformBar {
+        WaveformBar {
+            min: -level,
+            max: level,
+            rms: level / 2.0,
+            clipped,
+            correlation: 0.25,
+        }
+    }
+
+    fn sample_peaks() -> Peaks {
+        Peaks {
+            mix: vec![bar(0.5, false), bar(1.0, true)],
+            channels: vec![
+                vec![bar(0.25, false), bar(1.0, true)],
+                vec![bar(0.75, false), bar(0.5, false)],
+            ],
+        }
+    }
+
+    fn levels(bars: &[WaveformBar]) -> Vec<(f32, f32, f32, f32, bool)> {
+        bars.iter()
+            .map(|b| (b.min, b.max, b.rms, b.correlation, b.clipped))
+            .collect()
+    }
+
+    #[test]
+    fn stored_peaks_load_back() {
+        let (cache, audio) = scratch("peak-cache-roundtrip");
+        let peaks = sample_peaks();
+        cache.store(&audio, 256, &peaks).unwrap();
+        let loaded = cache.load(&audio, 256);
+        let other_resolution = cache.load(&audio, 512);
+        fs::remove_dir_all(audio.parent().unwrap()).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(levels(&loaded.mix), levels(&peaks.mix));
+        assert_eq!(loaded.channels.len(), 2);
+        for (loaded, stored) in loaded.channels.iter().zip(&peaks.channels) {
+            assert_eq!(levels(loaded), levels(stored));
+        }
+        assert!(other_resolution.is_none());
+    }
+
+    #[test]
+    fn damaged_or_stale_files_are_ignored() {
+        let (cache, audio) = scratch("peak-cache-stale");
+        cache.store(&audio, 256, &sample_peaks()).unwrap();
+        let path = cache.dir.join(file_name(&source_key(&audio).unwrap()));
+
+        let mut data = fs::read(&path).unwrap();
+        data[MAGIC.len() + 20] ^= 0xFF;
+        fs::write(&path, &data).unwrap();
+        let damaged = cache.load(&audio, 256);
+
+        cache.store(&audio, 256, &sample_peaks()).unwrap();
+        fs::write(&audio, b"re-encoded, and a different size").unwrap();
+        let stale = cache.load(&audio, 256);
+        fs::remove_dir_all(audio.parent().unwrap()).unwrap();
+
+        assert!(damaged.is_none());
+        assert!(stale.is_none());
+    }
+
+    #[test]
+    fn the_least_recently_used_files_are_evicted() {
+        let (cache, audio) = scratch("peak-cache-evict");
+        let dir = audio.parent().unwrap().to_path_buf();
+        let songs: Vec<PathBuf> = (0..3).map(|i| dir.join(format!("{}.wav", i))).collect();
+        let now = SystemTime::now();
+        for (i, song) in songs.iter().enumerate() {
+            fs::write(song, vec![0; i + 1]).unwrap();
+            cache.store(song, 256, &sample_peaks()).unwrap();
+            let path = cache.dir.join(file_name(&source_key(song).unwrap()));
+            let age = std::time::Duration::from_secs(100 - i as u64 * 10);
+            let file = fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(now - age).unwrap();
+        }
+
+        // Playing the oldest makes the second the least recently used.
+        assert!(cache.load(&songs[0], 256).is_some());
+        let size = fs::metadata(cache.dir.join(file_name(&source_key(&songs[0]).unwrap())))
+            .unwrap()
+            .len();
+        cache.evict(size * 2).unwrap();
+        let kept: Vec<bool> = songs
+            .iter()
+            .map(|song| cache.load(song, 256).is_some())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(kept, [true, false, true]);
+    }
+}
@@ -1,13 +1,22 @@
 use rodio::{Decoder, OutputStream, Sink, Source};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::spectrum::SpectrumAnalyzer;
-use crate::tee_source::TeeSource;
-use crate::waveform::{self, WaveformData};
+use crate::cover_art;
+use crate::lyrics;
+use crate::metadata::{self, TrackMetadata};
+use crate::peak_cache::PeakCache;
+use crate::playlist::{Playlist, RepeatMode, Track};
+use crate::spectrum::{SpectrumAnalyzer, SpectrumSettings};
+use crate::tee_source::{SampleTap, TeeSource};
+use crate::track_source::{TrackHandle, TrackSource};
+use crate::waveform::{self, WaveformData, WaveformJob};
+
+/// How far into a track "previous" restarts it instead of going back.
+const RESTART_THRESHOLD: Duration = Duration::from_secs(3);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PlaybackState {
@@ -15,61 +24,394 @@ pub enum PlaybackState {
     Paused,
 }
 
+/// A decoder that has been appended to the sink, and the track it is
+/// currently playing. With a cue sheet one decoder plays through several
+/// consecutive tracks of the same file.
+struct QueuedTrack {
+    index: usize,
+    duration: Duration,
+    waveform: WaveformData,
+    metadata: TrackMetadata,
+    file_duration: Duration,
+    file_waveform: WaveformData,
+    waveform_job: Option<WaveformJob>,
+    // The job version `file_waveform` was taken from.
+    waveform_version: u64,
+    file_metadata: TrackMetadata,
+    handle: Arc<TrackHandle>,
+}
+
+impl QueuedTrack {
+    /// Switches to `track` (at `index`), which lives in the same file.
+    fn enter(&mut self, index: usize, track: &Track) {
+        self.index = index;
+        self.metadata = self.file_metadata.for_track(track);
+
+        let end = track.end.unwrap_or(self.file_duration);
+        self.duration = if end > track.start {
+            end - track.start
+        } else {
+            track.duration.unwrap_or(Duration::ZERO)
+        };
+
+        self.slice_waveform(track);
+    }
+
+    /// Picks up a more complete waveform from the worker, returning whether
+    /// there was one.
+    fn refresh_waveform(&mut self, track: &Track) -> bool {
+        let Some(job) = &self.waveform_job else {
+            return false;
+        };
+        let version = job.version();
+        if version == self.waveform_version {
+            return false;
+        }
+
+        self.waveform_version = version;
+        self.file_waveform = job.data();
+        self.slice_waveform(track);
+        true
+    }
+
+    fn slice_waveform(&mut self, track: &Track) {
+        let end = track.end.unwrap_or(self.file_duration);
+        let file_secs = self.file_duration.as_secs_f64();
+        self.waveform = if !track.is_cue_part() || file_secs == 0.0 {
+            self.file_waveform.clone()
+        } else {
+            self.file_waveform.slice(
+                track.start.as_secs_f64() / file_secs,
+                end.as_secs_f64() / file_secs,
+            )
+        };
+    }
+}
+
 pub struct Player {
     _stream: OutputStream,
     sink: Arc<Sink>,
     state: Arc<Mutex<PlaybackState>>,
-    duration: Duration,
-    waveform: WaveformData,
+    playlist: Playlist,
+    current: QueuedTrack,
+    // Queued on the sink behind `current` so it starts without a gap.
+    upcoming: Option<QueuedTrack>,
+    // The last waveform job started, so repeating a file or jumping around a
+    // cue sheet doesn't decode the whole file again.
+    waveform_cache: Option<(PathBuf, WaveformJob)>,
+    // Set when the current waveform has filled in further.
+    waveform_changed: bool,
+    peak_cache: Option<PeakCache>,
+    // Created the first time a visualizer needs it.
     spectrum: Option<Arc<Mutex<SpectrumAnalyzer>>>,
+    spectrum_settings: SpectrumSettings,
+    sample_tap: SampleTap,
     pub volume_step: f32,
     pub seek_step: i64,
 }
 
 impl Player {
-    pub fn new<P: AsRef<Path>>(
-        path: P,
-        enhanced_waveform: bool,
-        spectrum_config: Option<(usize, f32, f32)>, // (num_bars, smoothing, bass_boost)
+    pub fn new(
+        playlist: Playlist,
+        peak_cache: Option<PeakCache>,
+        spectrum_settings: SpectrumSettings,
         volume_step: f32,
         seek_step: i64,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let (_stream, stream_handle) = OutputStream::try_default()?;
         let sink = Sink::try_new(&stream_handle)?;
+        sink.pause();
+
+        let placeholder = WaveformData::empty();
+        let mut player = Player {
+            _stream,
+            sink: Arc::new(sink),
+            state: Arc::new(Mutex::new(PlaybackState::Paused)),
+            playlist,
+            current: QueuedTrack {
+                index: 0,
+                duration: Duration::ZERO,
+                waveform: placeholder.clone(),
+                metadata: TrackMetadata::default(),
+                file_duration: Duration::ZERO,
+                file_waveform: placeholder,
+                waveform_job: None,
+                waveform_version: 0,
+                file_metadata: TrackMetadata::default(),
+                handle: Arc::default(),
+            },
+            upcoming: None,
+            waveform_cache: None,
+            waveform_changed: false,
+            peak_cache,
+            spectrum: None,
+            spectrum_settings,
+            sample_tap: SampleTap::default(),
+            volume_step,
+            seek_step,
+        };
+
+        // Skip over leading entries that fail to decode rather than giving up
+        // on the whole queue.
+        let mut index = player.playlist.first_index();
+        for _ in 0..player.playlist.len() {
+            let Some(i) = index else { break };
+            match player.queue(i) {
+                Ok(track) => {
+                    player.current = track;
+                    player.preload_next();
+                    return Ok(player);
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Warning: skipping {}: {}",
+                        player.playlist.get(i).path.display(),
+                        e
+                    );
+                    index = player.playlist.next_index(i);
+                }
+            }
+        }
+
+        Err("No playable audio files in queue".into())
+    }
+
+    /// Opens the track at `index` and appends it to the end of the sink.
+    fn queue(&mut self, index: usize) -> Result<QueuedTrack, Box<dyn std::error::Error>> {
+        let track = self.playlist.get(index);
+        let path = track.path.clone();
+        let start = track.start;
 
         let file = File::open(&path)?;
         let source = Decoder::new(BufReader::new(file))?;
+        let file_duration = source
+            .total_duration()
+            .or(track.duration.filter(|_| track.end.is_none()))
+            .unwrap_or(Duration::ZERO);
+        let handle = Arc::new(TrackHandle::default());
 
-        let duration = source.total_duration().unwrap_or(Duration::from_secs(0));
+        let tee_source = TeeSource::new(source.convert_samples(), Arc::clone(&self.sample_tap));
+        self.sink
+            .append(TrackSource::new(tee_source, Arc::clone(&handle), start));
 
-        let spectrum = if let Some((num_bars, smoothing, bass_boost)) = spectrum_config {
-            let analyzer = Arc::new(Mutex::new(SpectrumAnalyzer::new(
-                num_bars, smoothing, bass_boost,
-            )));
-            let sample_buffer = analyzer.lock().unwrap().get_sample_buffer();
-            let tee_source = TeeSource::new(source.convert_samples(), sample_buffer);
-            sink.append(tee_source);
-            Some(analyzer)
-        } else {
-            sink.append(source);
-            None
+        let waveform_job = self.waveform_job(&path);
+        let file_waveform = waveform_job.data();
+        let mut file_metadata = metadata::probe(&path).unwrap_or_default();
+        if file_metadata.cover.is_none() {
+            file_metadata.cover = cover_art::find_sidecar(&path);
+        }
+        if let Some(lyrics) = lyrics::load(&path) {
+            file_metadata.lyrics = Some(lyrics);
+        }
+        let mut queued = QueuedTrack {
+            index,
+            duration: Duration::ZERO,
+            waveform: file_waveform.clone(),
+            metadata: file_metadata.clone(),
+            file_duration,
+            file_waveform,
+            waveform_version: waveform_job.version(),
+            waveform_job: Some(waveform_job),
+            file_metadata,
+            handle,
         };
+        queued.enter(index, self.playlist.get(index));
 
-        sink.pause();
+        Ok(queued)
+    }
 
-        let waveform = waveform::generate_waveform(&path, 100, enhanced_waveform)
-            .unwrap_or_else(|_| WaveformData::new(vec![0.0; 100], false));
+    fn waveform_job(&mut self, path: &Path) -> WaveformJob {
+        if let Some((cached_path, job)) = &self.waveform_cache
+            && cached_path == path
+        {
+            return job.clone();
+        }
 
-        Ok(Player {
-            _stream,
-            sink: Arc::new(sink),
-            state: Arc::new(Mutex::new(PlaybackState::Paused)),
-            duration,
-            waveform,
-            spectrum,
-            volume_step,
-            seek_step,
-        })
+        let job = waveform::spawn_waveform(path, self.peak_cache.clone());
+
+        self.waveform_cache = Some((path.to_path_buf(), job.clone()));
+        job
+    }
+
+    /// Queues the first track from `index` onwards that opens successfully.
+    fn queue_playable(&mut self, mut index: Option<usize>) -> Option<QueuedTrack> {
+        for _ in 0..self.playlist.len() {
+            let i = index?;
+            if let Ok(track) = self.queue(i) {
+                return Some(track);
+            }
+            index = self.playlist.next_index(i);
+        }
+        None
+    }
+
+    /// Queues the track after the current one so it plays gaplessly. When
+    /// that is the next cue track of the same file the current decoder just
+    /// keeps going instead.
+    fn preload_next(&mut self) {
+        let current = self.current.index;
+        self.playlist.enter(current);
+        let next = self.playlist.upcoming_index(current);
+
+        if let Some(next) = next
+            && self.playlist.is_continuation(current, next)
+        {
+            self.current.handle.set_stop_at(None);
+            self.upcoming = None;
+            return;
+        }
+
+        self.current
+            .handle
+            .set_stop_at(self.playlist.get(current).end);
+        self.upcoming = self.queue_playable(next);
+    }
+
+    /// Replaces the preloaded track after the play order has changed.
+    fn replan(&mut self) {
+        self.update();
+        if let Some(track) = self.upcoming.take() {
+            track.handle.cancel();
+        }
+        self.preload_next();
+    }
+
+    /// Drops everything on the sink and starts over from the track at
+    /// `index`, or the first playable track after it.
+    fn jump_to(&mut self, index: usize) -> bool {
+        self.sink.clear();
+        self.upcoming = None;
+
+        let loaded = match self.queue_playable(Some(index)) {
+            Some(track) => {
+                self.current = track;
+                true
+            }
+            None => false,
+        };
+
+        // `Sink::clear` pauses the sink, so resume if we were already playing.
+        if self.state() == PlaybackState::Playing {
+            self.sink.play();
+        }
+
+        if loaded {
+            self.preload_next();
+        }
+        loaded
+    }
+
+    /// Moves to another track in the file that is already playing by seeking
+    /// the current decoder.
+    fn seek_to_track(&mut self, index: usize) {
+        if let Some(track) = self.upcoming.take() {
+            track.handle.cancel();
+        }
+        // Lift the old stop point first so seeking past it doesn't end the
+        // source.
+        self.current.handle.set_stop_at(None);
+        self.sink.try_seek(self.playlist.get(index).start).ok();
+        self.current.enter(index, self.playlist.get(index));
+        self.preload_next();
+    }
+
+    fn in_current_file(&self, index: usize) -> bool {
+        self.playlist.get(index).path == self.playlist.get(self.current.index).path
+    }
+
+    /// Promotes the preloaded track once the sink has moved on to it, or the
+    /// next cue track once playback crosses into it. Call this every tick so
+    /// the UI swaps tracks when the audio does.
+    pub fn update(&mut self) {
+        self.follow_playback();
+
+        let track = self.playlist.get(self.current.index);
+        if self.current.refresh_waveform(track) {
+            self.waveform_changed = true;
+        }
+    }
+
+    /// Moves `current` on to whichever track is now audible.
+    fn follow_playback(&mut self) {
+        let switched = self
+            .upcoming
+            .as_ref()
+            .is_some_and(|track| track.handle.has_started());
+
+        if switched && let Some(track) = self.upcoming.take() {
+            self.current = track;
+            self.preload_next();
+            return;
+        }
+
+        let current = self.current.index;
+        if let Some(end) = self.playlist.get(current).end
+            && self.current.handle.position() >= end
+            && let Some(next) = self.playlist.upcoming_index(current)
+            && self.playlist.is_continuation(current, next)
+        {
+            self.current.enter(next, self.playlist.get(next));
+            self.preload_next();
+        }
+    }
+
+    pub fn next_track(&mut self) {
+        let Some(next) = self.playlist.next_index(self.current.index) else {
+            return;
+        };
+
+        match self.upcoming.take() {
+            Some(track) if track.index == next => {
+                self.sink.skip_one();
+                self.current = track;
+                self.preload_next();
+            }
+            upcoming => {
+                self.upcoming = upcoming;
+                if self.in_current_file(next) {
+                    self.seek_to_track(next);
+                } else {
+                    self.jump_to(next);
+                }
+            }
+        }
+    }
+
+    /// Restarts the current track, or goes back one track when we are near
+    /// its beginning.
+    pub fn previous_track(&mut self) {
+        let previous = self.playlist.previous_index(self.current.index);
+        match previous {
+            Some(index) if self.position() < RESTART_THRESHOLD => {
+                if self.in_current_file(index) {
+                    self.seek_to_track(index);
+                } else {
+                    self.jump_to(index);
+                }
+            }
+            _ => {
+                self.sink.try_seek(self.track_start()).ok();
+            }
+        }
+    }
+
+    pub fn toggle_shuffle(&mut self) {
+        let shuffle = !self.playlist.shuffle();
+        self.playlist.set_shuffle(shuffle, Some(self.current.index));
+        self.replan();
+    }
+
+    pub fn cycle_repeat(&mut self) {
+        self.playlist.set_repeat(self.playlist.repeat().cycle());
+        self.replan();
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.playlist.shuffle()
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.playlist.repeat()
     }
 
     pub fn play(&self) {
@@ -101,24 +443,35 @@ impl Player {
     pub fn seek(&self, offset: i64) {
         let current = self.position().as_secs() as i64;
         let new_position = (current + offset).max(0) as u64;
-        let duration = self.duration.as_secs();
+        self.seek_to(Duration::from_secs(new_position));
+    }
 
-        if new_position < duration {
-            self.sink.try_seek(Duration::from_secs(new_position)).ok();
+    /// Jumps to `position` within the current track, if it is inside it.
+    pub fn seek_to(&self, position: Duration) {
+        if position < self.current.duration {
+            self.sink.try_seek(self.track_start() + position).ok();
         }
     }
 
     pub fn restart(&self) {
-        self.sink.try_seek(Duration::from_secs(0)).ok();
+        self.sink.try_seek(self.track_start()).ok();
         self.play();
     }
 
+    /// Where the current track begins in its file; non-zero for cue tracks.
+    fn track_start(&self) -> Duration {
+        self.playlist.get(self.current.index).start
+    }
+
     pub fn position(&self) -> Duration {
-        self.sink.get_pos()
+        self.current
+            .handle
+            .position()
+            .saturating_sub(self.track_start())
     }
 
     pub fn duration(&self) -> Duration {
-        self.duration
+        self.current.duration
     }
 
     pub fn state(&self) -> PlaybackState {
@@ -129,11 +482,51 @@ impl Player {
         self.sink.empty()
     }
 
+    pub fn current_title(&self) -> String {
+        self.playlist.get(self.current.index).display_name()
+    }
+
+    pub fn save_playlist<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        self.playlist.save(path)
+    }
+
+    pub fn track_index(&self) -> usize {
+        self.current.index
+    }
+
+    /// Where the current track comes in the play order, which differs from
+    /// `track_index` while shuffling.
+    pub fn track_position(&self) -> usize {
+        self.playlist.position(self.current.index)
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.playlist.len()
+    }
+
     pub fn waveform(&self) -> &WaveformData {
-        &self.waveform
+        &self.current.waveform
+    }
+
+    /// True once after the current waveform has filled in further.
+    pub fn take_waveform_update(&mut self) -> bool {
+        std::mem::take(&mut self.waveform_changed)
+    }
+
+    pub fn metadata(&self) -> &TrackMetadata {
+        &self.current.metadata
     }
 
-    pub fn spectrum(&self) -> Option<Arc<Mutex<SpectrumAnalyzer>>> {
-        self.spectrum.as_ref().map(Arc::clone)
+    /// The analyzer, created the first time it's asked for. The track that's
+    /// playing starts feeding it straight away.
+    pub fn spectrum(&mut self) -> Arc<Mutex<SpectrumAnalyzer>> {
+        let analyzer = self.spectrum.get_or_insert_with(|| {
+            Arc::new(Mutex::new(SpectrumAnalyzer::new(
+                self.spectrum_settings.clone(),
+            )))
+        });
+        // Only ever set here, after the analyzer exists.
+        let _ = self.sample_tap.set(analyzer.lock().unwrap().sample_ring());
+        Arc::clone(analyzer)
     }
 }
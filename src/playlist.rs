@@ -0,0 +1,361 @@
+use rand::seq::SliceRandom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cue;
+use crate::playlist_file;
+
+const SUPPORTED_EXTENSIONS: &[&str] = &["mp3", "wav", "flac", "ogg", "oga", "aac", "m4a"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepeatMode {
+    Off,
+    One,
+    All,
+}
+
+impl RepeatMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" => Some(RepeatMode::Off),
+            "one" => Some(RepeatMode::One),
+            "all" => Some(RepeatMode::All),
+            _ => None,
+        }
+    }
+
+    pub fn cycle(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+}
+
+pub struct Track {
+    pub path: PathBuf,
+    // From `#EXTINF`, PLS `TitleN`/`LengthN` or a cue sheet.
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration: Option<Duration>,
+    // Where the track starts and ends within `path`. Only cue sheet tracks
+    // cover part of a file; `end` is `None` when the track runs to the end.
+    pub start: Duration,
+    pub end: Option<Duration>,
+}
+
+impl Track {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            title: None,
+            artist: None,
+            duration: None,
+            start: Duration::ZERO,
+            end: None,
+        }
+    }
+
+    /// True for a cue sheet track covering only part of its file.
+    pub fn is_cue_part(&self) -> bool {
+        !self.start.is_zero() || self.end.is_some()
+    }
+
+    pub fn display_name(&self) -> String {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => return format!("{} - {}", artist, title),
+            (None, Some(title)) => return title.clone(),
+            _ => {}
+        }
+        self.path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("Unknown")
+            .to_string()
+    }
+}
+
+pub struct Playlist {
+    tracks: Vec<Track>,
+    // Play order as indices into `tracks`; a permutation when shuffling.
+    order: Vec<usize>,
+    // The order for the next pass, drawn when shuffling and repeating once
+    // something asks what comes after the end of this one.
+    next_order: Option<Vec<usize>>,
+    // The track playing, as last told by `enter`.
+    current: Option<usize>,
+    shuffle: bool,
+    repeat: RepeatMode,
+}
+
+impl Playlist {
+    pub fn from_paths<P: AsRef<Path>>(paths: &[P]) -> io::Result<Self> {
+        let mut tracks = Vec::new();
+
+        for path in paths {
+            let path = path.as_ref();
+            if path.is_dir() {
+                collect_dir(path, &mut tracks)?;
+            } else if playlist_file::is_playlist_file(path) {
+                tracks.extend(playlist_file::load(path)?);
+            } else if cue::is_cue_file(path) {
+                tracks.extend(cue::load(path, None)?);
+            } else {
+                push_file(Track::new(path.to_path_buf()), &mut tracks);
+            }
+        }
+
+        Ok(Self::new(tracks))
+    }
+
+    fn new(tracks: Vec<Track>) -> Self {
+        Self {
+            order: (0..tracks.len()).collect(),
+            tracks,
+            next_order: None,
+            current: None,
+            shuffle: false,
+            repeat: RepeatMode::Off,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn get(&self, index: usize) -> &Track {
+        &self.tracks[index]
+    }
+
+    /// True when `next` picks up in the same file exactly where `index` ends,
+    /// so one decoder can play straight through both.
+    pub fn is_continuation(&self, index: usize, next: usize) -> bool {
+        let (track, next) = (&self.tracks[index], &self.tracks[next]);
+        track.path == next.path && track.end == Some(next.start)
+    }
+
+    /// Writes the queue, in play order, as an M3U8 playlist.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let tracks: Vec<&Track> = self.order.iter().map(|&i| &self.tracks[i]).collect();
+        playlist_file::save_m3u8(path.as_ref(), &tracks)
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    /// Turns shuffle on or off. A fresh permutation is drawn when enabling,
+    /// with `current` moved to the front so every other track plays before
+    /// it comes round again.
+    pub fn set_shuffle(&mut self, shuffle: bool, current: Option<usize>) {
+        self.shuffle = shuffle;
+        self.next_order = None;
+
+        if shuffle {
+            self.order.shuffle(&mut rand::rng());
+            if let Some(current) = current
+                && let Some(pos) = self.order.iter().position(|&i| i == current)
+            {
+                self.order.swap(0, pos);
+            }
+        } else {
+            self.order.sort_unstable();
+        }
+    }
+
+    /// Index of the first track to play.
+    pub fn first_index(&self) -> Option<usize> {
+        self.order.first().copied()
+    }
+
+    /// Index of the track that plays after `index` when skipping forward.
+    /// Repeating a shuffled queue, each pass gets a fresh order that doesn't
+    /// start with the track the last one ended on.
+    pub fn next_index(&mut self, index: usize) -> Option<usize> {
+        let pos = self.position(index);
+        match self.order.get(pos + 1) {
+            Some(&next) => Some(next),
+            None if self.repeat == RepeatMode::All && self.shuffle => {
+                let next_order = self.next_order.get_or_insert_with(|| {
+                    let mut order = self.order.clone();
+                    order.shuffle(&mut rand::rng());
+                    if order.len() > 1 && order[0] == index {
+                        let last = order.len() - 1;
+                        order.swap(0, last);
+                    }
+                    order
+                });
+                next_order.first().copied()
+            }
+            None if self.repeat == RepeatMode::All => self.first_index(),
+            None => None,
+        }
+    }
+
+    /// Notes that `index` is now playing, switching to the next pass's order
+    /// when it follows on from the end of this one.
+    pub fn enter(&mut self, index: usize) {
+        let at_end = self.current.is_some() && self.current == self.order.last().copied();
+        if at_end
+            && let Some(next_order) = self
+                .next_order
+                .take_if(|order| order.first() == Some(&index))
+        {
+            self.order = next_order;
+        }
+        self.current = Some(index);
+    }
+
+    /// Index of the track that plays after `index` when it ends on its own.
+    pub fn upcoming_index(&mut self, index: usize) -> Option<usize> {
+        if self.repeat == RepeatMode::One {
+            Some(index)
+        } else {
+            self.next_index(index)
+        }
+    }
+
+    /// Index of the track that played before `index`, if any.
+    pub fn previous_index(&self, index: usize) -> Option<usize> {
+        match self.position(index).checked_sub(1) {
+            Some(pos) => Some(self.order[pos]),
+            None if self.repeat == RepeatMode::All => self.order.last().copied(),
+            None => None,
+        }
+    }
+
+    /// Where `index` comes in the play order.
+    pub fn position(&self, index: usize) -> usize {
+        self.order.iter().position(|&i| i == index).unwrap_or(0)
+    }
+}
+
+pub fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            SUPPORTED_EXTENSIONS
+                .iter()
+                .any(|supported| ext.eq_ignore_ascii_case(supported))
+        })
+}
+
+fn collect_dir(dir: &Path, tracks: &mut Vec<Track>) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_dir(&path, tracks)?;
+        } else if is_supported(&path) {
+            push_file(Track::new(path), tracks);
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds a whole audio file, split into its cue sheet tracks when it has one.
+pub fn push_file(track: Track, tracks: &mut Vec<Track>) {
+    if let Some(sheet) = cue::find_sheet(&track.path) {
+        match cue::load(&sheet, Some(&track.path)) {
+            Ok(cue_tracks) if !cue_tracks.is_empty() => {
+                tracks.extend(cue_tracks);
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: ignoring {}: {}", sheet.display(), e),
+        }
+    }
+    tracks.push(track);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist(count: usize) -> Playlist {
+        Playlist::new(
+            (0..count)
+                .map(|i| Track::new(PathBuf::from(format!("{}.flac", i))))
+                .collect(),
+        )
+    }
+
+    /// Plays `count` tracks from the first, the way the player moves on.
+    fn play(playlist: &mut Playlist, count: usize) -> Vec<usize> {
+        let mut index = playlist.first_index().unwrap();
+        playlist.enter(index);
+        let mut played = vec![index];
+        while played.len() < count {
+            index = playlist.upcoming_index(index).unwrap();
+            playlist.enter(index);
+            played.push(index);
+        }
+        played
+    }
+
+    #[test]
+    fn repeated_shuffles_play_every_track_once_per_pass() {
+        let mut playlist = playlist(8);
+        playlist.set_shuffle(true, None);
+        playlist.set_repeat(RepeatMode::All);
+
+        let played = play(&mut playlist, 8 * 6);
+        let passes: Vec<&[usize]> = played.chunks(8).collect();
+        for pass in &passes {
+            let mut sorted = pass.to_vec();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..8).collect::<Vec<_>>());
+        }
+        for pair in passes.windows(2) {
+            assert_ne!(pair[0][7], pair[1][0]);
+        }
+        assert!(passes.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn toggling_shuffle_keeps_the_current_track() {
+        let mut playlist = playlist(10);
+        playlist.set_shuffle(true, Some(6));
+        assert_eq!(playlist.position(6), 0);
+
+        playlist.set_shuffle(false, Some(6));
+        assert_eq!(playlist.position(6), 6);
+        assert_eq!(playlist.next_index(6), Some(7));
+    }
+
+    #[test]
+    fn repeat_modes_at_the_ends_of_the_queue() {
+        let mut playlist = playlist(3);
+        assert_eq!(playlist.next_index(2), None);
+        assert_eq!(playlist.previous_index(0), None);
+        assert_eq!(playlist.upcoming_index(1), Some(2));
+
+        playlist.set_repeat(RepeatMode::All);
+        assert_eq!(playlist.next_index(2), Some(0));
+        assert_eq!(playlist.previous_index(0), Some(2));
+
+        playlist.set_repeat(RepeatMode::One);
+        assert_eq!(playlist.upcoming_index(1), Some(1));
+        assert_eq!(playlist.next_index(1), Some(2));
+        assert_eq!(playlist.next_index(2), None);
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cue;
+use crate::playlist::{self, Track};
+use crate::text_file;
+
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["m3u", "m3u8", "pls"]
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Reads an M3U, M3U8 or PLS playlist. Relative entries are resolved against
+/// the playlist's own directory, and entries that don't exist are skipped
+/// with a warning. Cue sheets, listed directly or found next to a file, are
+/// split into their tracks.
+pub fn load(path: &Path) -> io::Result<Vec<Track>> {
+    let text = text_file::read(path)?;
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let is_pls = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pls"));
+
+    let entries = if is_pls {
+        parse_pls(&text)
+    } else {
+        parse_m3u(&text)
+    };
+
+    let mut tracks = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if entry.location.contains("://") && !entry.location.starts_with("file://") {
+            eprintln!(
+                "Warning: skipping {}: streams are not supported",
+                entry.location
+            );
+            continue;
+        }
+
+        let track_path = resolve(base, &entry.location);
+        if !track_path.is_file() {
+            eprintln!("Warning: skipping {}: file not found", track_path.display());
+            continue;
+        }
+
+        if cue::is_cue_file(&track_path) {
+            match cue::load(&track_path, None) {
+                Ok(cue_tracks) => tracks.extend(cue_tracks),
+                Err(e) => eprintln!("Warning: skipping {}: {}", track_path.display(), e),
+            }
+            continue;
+        }
+
+        let mut track = Track::new(track_path);
+        track.title = entry.title;
+        track.duration = entry.duration;
+        playlist::push_file(track, &mut tracks);
+    }
+
+    Ok(tracks)
+}
+
+/// Writes `tracks` as an extended M3U8 playlist. Tracks below the playlist's
+/// directory are stored relative to it so the playlist can be moved along
+/// with the music. M3U has no way to address part of a file, so a file split
+/// by a cue sheet is written once, where its first track comes, and picks
+/// the sheet up again when loaded.
+pub fn save_m3u8(path: &Path, tracks: &[&Track]) -> io::Result<()> {
+    let base = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .canonicalize()?;
+
+    let mut out = String::from("#EXTM3U\n");
+    let mut split_files = HashSet::new();
+    for track in tracks {
+        if track.is_cue_part() {
+            if !split_files.insert(&track.path) {
+                continue;
+            }
+            out.push_str("#EXTINF:-1,");
+            out.push_str(&Track::new(track.path.clone()).display_name());
+            out.push('\n');
+        } else {
+            let seconds = track.duration.map_or(-1, |d| d.as_secs() as i64);
+            out.push_str(&format!("#EXTINF:{},{}\n", seconds, track.display_name()));
+        }
+
+        let absolute = track
+            .path
+            .canonicalize()
+            .unwrap_or_else(|_| track.path.clone());
+        let location = absolute.strip_prefix(&base).unwrap_or(&absolute);
+        out.push_str(&location.to_string_lossy());
+        out.push('\n');
+    }
+
+    let mut file = fs::File::create(path)?;
+    file.write_all(out.as_bytes())
+}
+
+struct Entry {
+    location: String,
+    title: Option<String>,
+    duration: Option<Duration>,
+}
+
+fn parse_m3u(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut pending: Option<(Option<Duration>, Option<String>)> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            pending = Some(parse_extinf(info));
+            continue;
+        }
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let (duration, title) = pending.take().unwrap_or((None, None));
+        entries.push(Entry {
+            location: line.to_string(),
+            title,
+            duration,
+        });
+    }
+
+    entries
+}
+
+/// Parses the `<seconds>[ attributes],<title>` part of an `#EXTINF` line.
+fn parse_extinf(info: &str) -> (Option<Duration>, Option<String>) {
+    let (head, title) = match info.split_once(',') {
+        Some((head, title)) => (head, Some(title.trim())),
+        None => (info, None),
+    };
+
+    let seconds = head
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse::<f64>().ok());
+    let duration = seconds.filter(|&s| s >= 0.0).map(Duration::from_secs_f64);
+    let title = title.filter(|t| !t.is_empty()).map(str::to_string);
+
+    (duration, title)
+}
+
+fn parse_pls(text: &str) -> Vec<Entry> {
+    let mut files: BTreeMap<u32, String> = BTreeMap::new();
+    let mut titles: BTreeMap<u32, String> = BTreeMap::new();
+    let mut lengths: BTreeMap<u32, Duration> = BTreeMap::new();
+
+    for line in text.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        if let Some(n) = key.strip_prefix("file").and_then(|n| n.parse().ok()) {
+            files.insert(n, value.to_string());
+        } else if let Some(n) = key.strip_prefix("title").and_then(|n| n.parse().ok()) {
+            titles.insert(n, value.to_string());
+        } else if let Some(n) = key.strip_prefix("length").and_then(|n| n.parse().ok())
+            && let Ok(secs) = value.parse::<f64>()
+            && secs >= 0.0
+        {
+            lengths.insert(n, Duration::from_secs_f64(secs));
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|(n, location)| Entry {
+            location,
+            title: titles.remove(&n).filter(|t| !t.is_empty()),
+            duration: lengths.remove(&n),
+        })
+        .collect()
+}
+
+fn resolve(base: &Path, location: &str) -> PathBuf {
+    let location = match location.strip_prefix("file://") {
+        Some(uri_path) => percent_decode(uri_path),
+        None => location.to_string(),
+    };
+
+    let path = PathBuf::from(location);
+    if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    }
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Some(byte) = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::Playlist;
+
+    #[test]
+    fn m3u_entries_take_the_preceding_extinf() {
+        let entries = parse_m3u(
+            "#EXTM3U\n\
+             #EXTINF:123 tvg-id=\"x\",Artist - Song\n\
+             song.flac\n\
+             \n\
+             # a comment\n\
+             other.mp3\n\
+             #EXTINF:-1,\n\
+             http://example.com/stream\n",
+        );
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].location, "song.flac");
+        assert_eq!(entries[0].title.as_deref(), Some("Artist - Song"));
+        assert_eq!(entries[0].duration, Some(Duration::from_secs(123)));
+        assert_eq!(entries[1].location, "other.mp3");
+        assert_eq!(entries[1].title, None);
+        assert_eq!(entries[1].duration, None);
+        assert_eq!(entries[2].title, None);
+        assert_eq!(entries[2].duration, None);
+    }
+
+    #[test]
+    fn pls_entries_are_ordered_by_number() {
+        let entries = parse_pls(
+            "[playlist]\n\
+             File2=b.ogg\n\
+             Title2=Second\n\
+             file1 = a.mp3\n\
+             Length1=61.5\n\
+             Length2=-1\n\
+             NumberOfEntries=2\n",
+        );
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].location, "a.mp3");
+        assert_eq!(entries[0].title, None);
+        assert_eq!(entries[0].duration, Some(Duration::from_secs_f64(61.5)));
+        assert_eq!(entries[1].location, "b.ogg");
+        assert_eq!(entries[1].title.as_deref(), Some("Second"));
+        assert_eq!(entries[1].duration, None);
+    }
+
+    #[test]
+    fn file_uris_are_percent_decoded() {
+        let base = Path::new("/music");
+        assert_eq!(
+            resolve(base, "file:///music/My%20Album/01%20%C3%A9t%C3%A9.flac"),
+            PathBuf::from("/music/My Album/01 été.flac")
+        );
+        assert_eq!(resolve(base, "a/b.mp3"), PathBuf::from("/music/a/b.mp3"));
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn cue_split_files_survive_a_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("apz-playlist-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("album.flac"), b"").unwrap();
+        fs::write(dir.join("single.mp3"), b"").unwrap();
+        fs::write(
+            dir.join("album.cue"),
+            "PERFORMER \"Band\"\n\
+             FILE \"album.flac\" WAVE\n\
+             \x20 TRACK 01 AUDIO\n\
+             \x20   TITLE \"One\"\n\
+             \x20   INDEX 01 00:00:00\n\
+             \x20 TRACK 02 AUDIO\n\
+             \x20   TITLE \"Two\"\n\
+             \x20   INDEX 01 03:00:00\n",
+        )
+        .unwrap();
+
+        let playlist =
+            Playlist::from_paths(&[dir.join("album.flac"), dir.join("single.mp3")]).unwrap();
+        assert_eq!(playlist.len(), 3);
+        let saved = dir.join("queue.m3u8");
+        playlist.save(&saved).unwrap();
+        let tracks = load(&saved).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<String> = tracks.iter().map(Track::display_name).collect();
+        assert_eq!(names, ["Band - One", "Band - Two", "single.mp3"]);
+        assert_eq!(tracks[1].start, Duration::from_secs(180));
+        assert!(tracks[2].start.is_zero() && tracks[2].end.is_none());
+    }
+
+    #[test]
+    fn shuffled_queues_are_saved_in_play_order() {
+        let dir = std::env::temp_dir().join(format!("apz-shuffle-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<PathBuf> = (0..6).map(|i| dir.join(format!("{}.mp3", i))).collect();
+        for path in &paths {
+            fs::write(path, b"").unwrap();
+        }
+
+        let mut playlist = Playlist::from_paths(&paths).unwrap();
+        playlist.set_shuffle(true, None);
+        let mut played = Vec::new();
+        let mut index = playlist.first_index();
+        while let Some(i) = index {
+            played.push(playlist.get(i).display_name());
+            index = playlist.next_index(i);
+        }
+
+        let saved = dir.join("queue.m3u8");
+        playlist.save(&saved).unwrap();
+        let tracks = load(&saved).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<String> = tracks.iter().map(Track::display_name).collect();
+        assert_eq!(names, played);
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering, fence};
+
+/// The most recent samples played, interleaved, written by the audio thread
+/// and read by the UI. Neither side ever waits for the other: the writer
+/// overwrites the oldest samples, and a reader that falls behind just tries
+/// again.
+///
+/// There must only be one writer at a time. Each track gets its own
+/// `TeeSource`, but the sink plays them one after another on the same
+/// thread, so that holds.
+pub struct SampleRing {
+    // f32 bit patterns, so plain atomics will do.
+    slots: Box<[AtomicU32]>,
+    // Samples written since the start, counting only whole frames, so a
+    // snapshot never starts partway through one.
+    written: AtomicUsize,
+    // Like `written`, but including the frame being written, and bumped
+    // before each slot is overwritten so a reader can tell when something
+    // it copied might have changed under it.
+    pending: AtomicUsize,
+    channels: AtomicU32,
+    sample_rate: AtomicU32,
+}
+
+/// How often `snapshot` retries when the writer laps it mid-copy.
+const SNAPSHOT_ATTEMPTS: usize = 4;
+
+impl SampleRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicUsize::new(0),
+            pending: AtomicUsize::new(0),
+            channels: AtomicU32::new(1),
+            sample_rate: AtomicU32::new(44100),
+        }
+    }
+
+    /// Describes the samples that follow.
+    pub fn set_format(&self, channels: u16, sample_rate: u32) {
+        self.channels
+            .store(channels.max(1) as u32, Ordering::Relaxed);
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels.load(Ordering::Relaxed) as usize
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    /// Samples published so far, counting from the start.
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Acquire)
+    }
+
+    /// Adds a sample; readers see it once its frame is complete.
+    pub fn push(&self, sample: f32, ends_frame: bool) {
+        let pending = self.pending.load(Ordering::Relaxed);
+        self.pending.store(pending + 1, Ordering::Relaxed);
+        // A reader that sees the new sample also sees `pending` bumped.
+        fence(Ordering::Release);
+        self.slots[pending % self.slots.len()].store(sample.to_bits(), Ordering::Relaxed);
+        if ends_frame {
+            // Publishes the slots along with the count.
+            self.written.store(pending + 1, Ordering::Release);
+        }
+    }
+
+    /// Fills `out` with the most recent samples, oldest first. Returns false,
+    /// leaving `out` alone, until enough have been written or if `out` is
+    /// larger than the ring.
+    pub fn snapshot(&self, out: &mut [f32]) -> bool {
+        let len = self.slots.len();
+        if out.len() > len {
+            return false;
+        }
+
+        for _ in 0..SNAPSHOT_ATTEMPTS {
+            let end = self.written.load(Ordering::Acquire);
+            let Some(start) = end.checked_sub(out.len()) else {
+                return false;
+            };
+
+            for (i, sample) in out.iter_mut().enumerate() {
+                *sample = f32::from_bits(self.slots[(start + i) % len].load(Ordering::Relaxed));
+            }
+
+            // Unless the writer has come all the way round to `start` in the
+            // meantime, nothing that was copied has been overwritten. The
+            // fence keeps this check after the copy.
+            fence(Ordering::Acquire);
+            if self.pending.load(Ordering::Relaxed) - start < len {
+                return true;
+            }
+        }
+
+        // Still usable for a display, just with a seam in it.
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_frames(ring: &SampleRing, samples: impl IntoIterator<Item = f32>, channels: usize) {
+        for (i, sample) in samples.into_iter().enumerate() {
+            ring.push(sample, (i + 1) % channels == 0);
+        }
+    }
+
+    #[test]
+    fn snapshots_wrap_around_the_end() {
+        let ring = SampleRing::new(8);
+        push_frames(&ring, (0..21).map(|i| i as f32), 1);
+
+        let mut out = [0.0; 6];
+        assert!(ring.snapshot(&mut out));
+        assert_eq!(out, [15.0, 16.0, 17.0, 18.0, 19.0, 20.0]);
+    }
+
+    #[test]
+    fn snapshots_wait_for_enough_samples() {
+        let ring = SampleRing::new(8);
+        push_frames(&ring, [1.0, 2.0, 3.0], 1);
+
+        let mut out = [9.0; 4];
+        assert!(!ring.snapshot(&mut out));
+        assert_eq!(out, [9.0; 4]);
+        assert!(ring.snapshot(&mut out[..3]));
+        assert_eq!(out[..3], [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn snapshots_no_larger_than_the_ring() {
+        let ring = SampleRing::new(8);
+        push_frames(&ring, (0..32).map(|i| i as f32), 1);
+
+        assert!(!ring.snapshot(&mut [0.0; 9]));
+        assert!(ring.snapshot(&mut [0.0; 8]));
+    }
+
+    #[test]
+    fn snapshots_never_start_mid_frame() {
+        let ring = SampleRing::new(16);
+        ring.set_format(2, 48000);
+        // Three whole frames, then the left half of a fourth.
+        push_frames(&ring, [1.0, -1.0, 2.0, -2.0, 3.0, -3.0], 2);
+        ring.push(4.0, false);
+
+        assert_eq!(ring.written(), 6);
+        let mut out = [0.0; 4];
+        assert!(ring.snapshot(&mut out));
+        assert_eq!(out, [2.0, -2.0, 3.0, -3.0]);
+
+        ring.push(-4.0, true);
+        assert!(ring.snapshot(&mut out));
+        assert_eq!(out, [3.0, -3.0, 4.0, -4.0]);
+    }
+}
@@ -1,68 +1,405 @@
-use rustfft::{FftPlanner, num_complex::Complex};
-use std::sync::{Arc, Mutex};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::ops::{Range, RangeInclusive};
+use std::sync::Arc;
+use std::time::Instant;
 
-const SAMPLE_SIZE: usize = 2048;
+use crate::sample_ring::SampleRing;
+
+/// FFT sizes on offer. Bigger ones resolve the bass better but react to
+/// changes more slowly.
+pub const FFT_SIZES: RangeInclusive<usize> = 1024..=16384;
+
+/// Most channels a snapshot is taken across.
+const MAX_CHANNELS: usize = 8;
+
+/// Samples kept for the analyzer. Room for more than the largest FFT's worth
+/// of every channel, so the audio thread rarely laps a snapshot in progress.
+const RING_CAPACITY: usize = *FFT_SIZES.end() * MAX_CHANNELS * 2;
+
+/// How fast the spectrogram scrolls, whatever the frame rate.
+const SPECTROGRAM_COLUMNS_PER_SECOND: usize = 40;
+
+/// Columns the spectrogram remembers, enough for a wide terminal.
+const SPECTROGRAM_HISTORY: usize = 512;
+
+/// Frequency rows kept for each spectrogram column, spaced like the bars.
+pub const SPECTROGRAM_BANDS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFunction {
+    Hann,
+    /// Leaks less into neighbouring bins than Hann, at the cost of wider
+    /// peaks.
+    BlackmanHarris,
+}
+
+impl WindowFunction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "hann" => Some(WindowFunction::Hann),
+            "blackman-harris" => Some(WindowFunction::BlackmanHarris),
+            _ => None,
+        }
+    }
+
+    fn coefficients(self, size: usize) -> Vec<f32> {
+        (0..size)
+            .map(|n| {
+                let x = 2.0 * PI * n as f32 / size as f32;
+                match self {
+                    WindowFunction::Hann => 0.5 - 0.5 * x.cos(),
+                    WindowFunction::BlackmanHarris => {
+                        0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos()
+                            - 0.01168 * (3.0 * x).cos()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct SpectrumSettings {
+    pub num_bars: usize,
+    /// Time constants, in milliseconds, for bars rising and falling.
+    pub attack_ms: f32,
+    pub decay_ms: f32,
+    /// How long a peak cap stays put before it drops, in milliseconds.
+    pub peak_hold_ms: f32,
+    /// How fast a dropping cap speeds up, in panel heights per second
+    /// squared.
+    pub peak_fall: f32,
+    pub bass_boost: f32,
+    /// A power of two within `FFT_SIZES`.
+    pub fft_size: usize,
+    pub window: WindowFunction,
+    /// Frequencies covered by the bars, in Hz.
+    pub min_freq: f32,
+    pub max_freq: f32,
+    /// Levels, in dBFS, shown as an empty and a full bar.
+    pub db_floor: f32,
+    pub db_ceiling: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bar {
+    /// From 0.0 at the floor to 1.0 at the ceiling.
+    pub level: f32,
+    /// Where the cap sits, never below `level`.
+    pub peak: f32,
+    // Seconds left before the cap starts to drop, and how fast it's dropping.
+    hold: f32,
+    velocity: f32,
+}
+
+impl Bar {
+    fn update(&mut self, level: f32, elapsed: f32, settings: &SpectrumSettings) {
+        let time_constant = if level > self.level {
+            settings.attack_ms
+        } else {
+            settings.decay_ms
+        };
+        // Frame-rate independent: after `time_constant` the bar has covered
+        // about two thirds of the way, however many updates that took.
+        let keep = (-elapsed * 1000.0 / time_constant.max(f32::EPSILON)).exp();
+        self.level = self.level * keep + level * (1.0 - keep);
+
+        if self.level >= self.peak {
+            self.peak = self.level;
+            self.hold = settings.peak_hold_ms / 1000.0;
+            self.velocity = 0.0;
+        } else if self.hold > 0.0 {
+            self.hold -= elapsed;
+        } else {
+            self.velocity += settings.peak_fall * elapsed;
+            self.peak = (self.peak - self.velocity * elapsed).max(self.level);
+        }
+    }
+}
 
 pub struct SpectrumAnalyzer {
-    samples: Arc<Mutex<Vec<f32>>>,
-    bars: Vec<f32>,
-    num_bars: usize,
-    smoothing: f32,
-    bass_boost: f32,
+    samples: Arc<SampleRing>,
+    settings: SpectrumSettings,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    // Turns FFT output into amplitudes. A sine's energy splits between the
+    // positive and negative frequencies, and the window takes away its own
+    // share.
+    amplitude_scale: f32,
+    // Bins covered by each bar, the frequency in the middle of them, and
+    // the sample rate they were worked out for.
+    bands: Vec<Range<usize>>,
+    bar_frequencies: Vec<f32>,
+    bands_rate: u32,
+    // One set of bars for the downmix, or one each for left and right when
+    // split.
+    bars: Vec<Vec<Bar>>,
+    // Whether the bars get `bass_boost`, which is left out while they're
+    // read against a dB scale.
+    boost_bass: bool,
+    last_update: Option<Instant>,
+
+    // Past columns of levels, oldest first, each with `SPECTROGRAM_BANDS`
+    // rows from low to high frequencies. `spectrogram_written` is how many
+    // samples the ring had taken when the last column was added.
+    spectrogram: VecDeque<Vec<f32>>,
+    spectrogram_bands: Vec<Range<usize>>,
+    spectrogram_written: usize,
+
+    // Kept between updates so the steady state doesn't allocate.
+    interleaved: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    magnitudes: Vec<f32>,
 }
 
 impl SpectrumAnalyzer {
-    pub fn new(num_bars: usize, smoothing: f32, bass_boost: f32) -> Self {
+    pub fn new(settings: SpectrumSettings) -> Self {
+        let size = settings.fft_size;
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        let window = settings.window.coefficients(size);
         Self {
-            samples: Arc::new(Mutex::new(Vec::new())),
-            bars: vec![0.0; num_bars],
-            num_bars,
-            smoothing,
-            bass_boost,
+            samples: Arc::new(SampleRing::new(RING_CAPACITY)),
+            amplitude_scale: 2.0 / window.iter().sum::<f32>(),
+            window,
+            buffer: vec![Complex::default(); size],
+            scratch: vec![Complex::default(); fft.get_inplace_scratch_len()],
+            magnitudes: vec![0.0; size / 2],
+            fft,
+            bands: Vec::new(),
+            bar_frequencies: Vec::new(),
+            bands_rate: 0,
+            bars: vec![vec![Bar::default(); settings.num_bars]],
+            boost_bass: true,
+            last_update: None,
+            spectrogram: VecDeque::with_capacity(SPECTROGRAM_HISTORY),
+            spectrogram_bands: Vec::new(),
+            spectrogram_written: 0,
+            interleaved: Vec::new(),
+            settings,
         }
     }
 
-    pub fn get_sample_buffer(&self) -> Arc<Mutex<Vec<f32>>> {
+    pub fn sample_ring(&self) -> Arc<SampleRing> {
         Arc::clone(&self.samples)
     }
 
+    /// Switches between analyzing a mono downmix and analyzing the left and
+    /// right channels separately.
+    pub fn set_split(&mut self, split: bool) {
+        let sets = if split { 2 } else { 1 };
+        if self.bars.len() != sets {
+            // Both sides start from the downmix, and the downmix from the
+            // left, so switching doesn't drop the bars to zero.
+            self.bars.resize(sets, self.bars[0].clone());
+        }
+    }
+
+    /// Applies `bass_boost` to the bars, or leaves them at their true level.
+    pub fn set_bass_boost(&mut self, boost: bool) {
+        self.boost_bass = boost;
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.bars.len() > 1
+    }
+
     pub fn update(&mut self) {
-        let samples = self.samples.lock().unwrap();
-        if samples.len() < SAMPLE_SIZE {
+        let now = Instant::now();
+        let elapsed = self
+            .last_update
+            .map_or(0.0, |last| now.duration_since(last).as_secs_f32());
+        self.last_update = Some(now);
+
+        let size = self.settings.fft_size;
+        let channels = self.samples.channels().min(MAX_CHANNELS);
+        self.interleaved.resize(size * channels, 0.0);
+        if !self.samples.snapshot(&mut self.interleaved) {
+            return;
+        }
+
+        self.refresh_bands(self.samples.sample_rate());
+
+        let sets = self.bars.len();
+        for set in 0..sets {
+            // A mono file shows the same channel on both sides.
+            let channel = (sets > 1).then(|| set.min(channels - 1));
+            self.transform(0, channels, channel);
+            self.update_bars(set, elapsed);
+        }
+    }
+
+    /// Adds a column to the spectrogram for every
+    /// `1 / SPECTROGRAM_COLUMNS_PER_SECOND` of audio played since the last
+    /// call, so it scrolls at the same speed however often it's drawn.
+    pub fn update_spectrogram(&mut self) {
+        let size = self.settings.fft_size;
+        let channels = self.samples.channels().min(MAX_CHANNELS);
+        let sample_rate = self.samples.sample_rate();
+        let hop = (sample_rate as usize / SPECTROGRAM_COLUMNS_PER_SECOND).max(1);
+
+        let written = self.samples.written();
+        let behind = written.saturating_sub(self.spectrogram_written) / channels;
+        if behind < hop {
+            // Paused, or not a whole column yet.
             return;
         }
+        // After a long gap, only catch up on what's still in the ring.
+        let fit = (RING_CAPACITY / 2 / channels).saturating_sub(size) / hop + 1;
+        let columns = (behind / hop).min(fit).min(SPECTROGRAM_HISTORY);
 
-        let mut buffer: Vec<Complex<f32>> = samples[..SAMPLE_SIZE]
-            .iter()
-            .map(|&s| Complex::new(s, 0.0))
-            .collect();
+        self.interleaved
+            .resize((size + (columns - 1) * hop) * channels, 0.0);
+        if !self.samples.snapshot(&mut self.interleaved) {
+            return;
+        }
+        self.spectrogram_written = written - behind % hop * channels;
+        self.refresh_bands(sample_rate);
 
-        let mut planner = FftPlanner::new();
-        let fft = planner.plan_fft_forward(SAMPLE_SIZE);
-        fft.process(&mut buffer);
+        for column in 0..columns {
+            self.transform(column * hop, channels, None);
 
-        let spectrum: Vec<f32> = buffer[..SAMPLE_SIZE / 2]
-            .iter()
-            .map(|c| (c.re * c.re + c.im * c.im).sqrt())
-            .collect();
+            let mut levels = if self.spectrogram.len() >= SPECTROGRAM_HISTORY {
+                self.spectrogram.pop_front().unwrap_or_default()
+            } else {
+                Vec::with_capacity(SPECTROGRAM_BANDS)
+            };
+            levels.clear();
+            levels.extend(
+                self.spectrogram_bands
+                    .iter()
+                    .map(|band| level(&self.magnitudes[band.clone()], 1.0, &self.settings)),
+            );
+            self.spectrogram.push_back(levels);
+        }
+    }
 
-        for (i, bar) in self.bars.iter_mut().enumerate() {
-            let freq_index = ((i as f32 / self.num_bars as f32).powf(1.3)
-                * (spectrum.len() - 1) as f32) as usize;
-            let freq_index = freq_index.min(spectrum.len() - 1);
+    /// Works out which bins the bars and spectrogram rows cover, if the
+    /// sample rate has changed since last time.
+    fn refresh_bands(&mut self, sample_rate: u32) {
+        if self.bands_rate != sample_rate {
+            self.bands = self.bands(self.settings.num_bars, sample_rate);
+            let bin_width = sample_rate as f32 / self.settings.fft_size as f32;
+            self.bar_frequencies = self
+                .bands
+                .iter()
+                .map(|band| (band.start + band.end - 1) as f32 / 2.0 * bin_width)
+                .collect();
+            self.spectrogram_bands = self.bands(SPECTROGRAM_BANDS, sample_rate);
+            self.bands_rate = sample_rate;
+        }
+    }
 
-            let bass_factor = self.bass_boost * (1.0 - i as f32 / self.num_bars as f32);
-            let amplitude = spectrum[freq_index] * (1.0 + bass_factor);
+    /// Fills `magnitudes` from `fft_size` frames of `interleaved`, starting
+    /// at frame `start`, of one channel or else the downmix. Scaled so a
+    /// full-scale sine peaks at 1.0.
+    fn transform(&mut self, start: usize, channels: usize, channel: Option<usize>) {
+        let frames = self.interleaved[start * channels..].chunks_exact(channels);
+        for ((out, frame), &w) in self.buffer.iter_mut().zip(frames).zip(&self.window) {
+            let sample = match channel {
+                Some(channel) => frame[channel],
+                None => frame.iter().sum::<f32>() / channels as f32,
+            };
+            *out = Complex::new(sample * w, 0.0);
+        }
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
 
-            *bar = *bar * self.smoothing + amplitude * (1.0 - self.smoothing);
+        for (magnitude, c) in self.magnitudes.iter_mut().zip(&self.buffer) {
+            *magnitude = c.norm() * self.amplitude_scale;
         }
     }
 
-    pub fn bars(&self) -> &[f32] {
+    /// The frequencies, in Hz, at the bottom of the first bar and the top of
+    /// the last, which can't go past what the sample rate allows.
+    fn frequency_range(&self, sample_rate: u32) -> (f32, f32) {
+        let max_freq = self.settings.max_freq.min(sample_rate as f32 / 2.0);
+        (self.settings.min_freq.min(max_freq), max_freq)
+    }
+
+    /// The range of bins each of `count` bands covers, spaced evenly on a
+    /// logarithmic scale from `min_freq` to `max_freq`.
+    fn bands(&self, count: usize, sample_rate: u32) -> Vec<Range<usize>> {
+        let size = self.settings.fft_size;
+        let bins = size / 2;
+        let bin_width = sample_rate as f32 / size as f32;
+        let (min_freq, max_freq) = self.frequency_range(sample_rate);
+        let edge = |i: usize| min_freq * (max_freq / min_freq).powf(i as f32 / count as f32);
+
+        (0..count)
+            .map(|i| {
+                let low = (edge(i) / bin_width).round() as usize;
+                let high = (edge(i + 1) / bin_width).round() as usize;
+                // Down in the bass a bar can be narrower than a bin, and
+                // takes the one it falls in.
+                let low = low.min(bins - 1);
+                low..high.clamp(low + 1, bins)
+            })
+            .collect()
+    }
+
+    fn update_bars(&mut self, set: usize, elapsed: f32) {
+        let settings = &self.settings;
+        let boost = self.bass_boost();
+
+        for (i, (bar, band)) in self.bars[set].iter_mut().zip(&self.bands).enumerate() {
+            let gain = bass_gain(i, boost, settings.num_bars);
+            let level = level(&self.magnitudes[band.clone()], gain, settings);
+            bar.update(level, elapsed, settings);
+        }
+    }
+
+    /// The downmix's bars, or the left and right channels' when split.
+    pub fn bars(&self) -> &[Vec<Bar>] {
         &self.bars
     }
 
-    pub fn num_bars(&self) -> usize {
-        self.num_bars
+    /// Levels, in dBFS, of an empty and a full bar.
+    pub fn db_range(&self) -> (f32, f32) {
+        (self.settings.db_floor, self.settings.db_ceiling)
     }
+
+    /// The frequency, in Hz, in the middle of the bins each bar shows. Down
+    /// in the bass neighbouring bars can share a bin, and so a frequency.
+    /// Empty until the first samples have come through.
+    pub fn bar_frequencies(&self) -> &[f32] {
+        &self.bar_frequencies
+    }
+
+    /// The level in dBFS that `level` stands for on bar `index`, without
+    /// the bar's bass boost.
+    pub fn bar_db(&self, index: usize, level: f32) -> f32 {
+        let settings = &self.settings;
+        let boosted = settings.db_floor + level * (settings.db_ceiling - settings.db_floor);
+        boosted - 20.0 * bass_gain(index, self.bass_boost(), settings.num_bars).log10()
+    }
+
+    fn bass_boost(&self) -> f32 {
+        if self.boost_bass {
+            self.settings.bass_boost
+        } else {
+            0.0
+        }
+    }
+
+    /// Past spectrogram columns, oldest first.
+    pub fn spectrogram(&self) -> &VecDeque<Vec<f32>> {
+        &self.spectrogram
+    }
+}
+
+/// How much `boost` lifts bar `index` of `count`, tapering off from the
+/// first bar to nothing at the last.
+fn bass_gain(index: usize, boost: f32, count: usize) -> f32 {
+    1.0 + boost * (1.0 - index as f32 / count as f32)
+}
+
+/// The average of `bins`, boosted by `gain`, as a fraction of the way from
+/// the floor to the ceiling.
+fn level(bins: &[f32], gain: f32, settings: &SpectrumSettings) -> f32 {
+    let magnitude = bins.iter().sum::<f32>() / bins.len() as f32;
+    let db = 20.0 * (magnitude * gain).max(1e-10).log10();
+    ((db - settings.db_floor) / (settings.db_ceiling - settings.db_floor)).clamp(0.0, 1.0)
 }
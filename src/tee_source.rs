@@ -1,19 +1,33 @@
 use rodio::Source;
-use std::sync::{Arc, Mutex};
+use rodio::source::SeekError;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
+use crate::sample_ring::SampleRing;
+
+/// Where tracks copy their samples to, once something is listening. Filled
+/// in when the analyzer is first created, so tracks that were already queued
+/// start feeding it too.
+pub type SampleTap = Arc<OnceLock<Arc<SampleRing>>>;
+
+/// Passes samples through unchanged, copying each into the tap's ring for the
+/// analyzers along with the channel layout.
 pub struct TeeSource<I> {
     input: I,
-    sample_buffer: Arc<Mutex<Vec<f32>>>,
-    buffer_size: usize,
+    tap: SampleTap,
+    // Taken from `tap` once it's been filled in.
+    samples: Option<Arc<SampleRing>>,
+    // Position within the current frame.
+    channel: u16,
 }
 
 impl<I> TeeSource<I> {
-    pub fn new(input: I, sample_buffer: Arc<Mutex<Vec<f32>>>) -> Self {
+    pub fn new(input: I, tap: SampleTap) -> Self {
         Self {
             input,
-            sample_buffer,
-            buffer_size: 2048,
+            tap,
+            samples: None,
+            channel: 0,
         }
     }
 }
@@ -25,17 +39,28 @@ where
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(sample) = self.input.next() {
-            let mut buffer = self.sample_buffer.lock().unwrap();
-            buffer.push(sample);
-            let len = buffer.len();
-            if len > self.buffer_size {
-                buffer.drain(0..len - self.buffer_size);
+        let channels = self.input.channels().max(1);
+        if self.channel == 0 {
+            // Only start copying at the start of a frame, and formats only
+            // change between frames.
+            if self.samples.is_none() {
+                self.samples = self.tap.get().cloned();
             }
-            Some(sample)
-        } else {
-            None
+            if let Some(samples) = &self.samples {
+                samples.set_format(channels, self.input.sample_rate());
+            }
+        }
+
+        let sample = self.input.next()?;
+        self.channel += 1;
+        let ends_frame = self.channel >= channels;
+        if ends_frame {
+            self.channel = 0;
+        }
+        if let Some(samples) = &self.samples {
+            samples.push(sample, ends_frame);
         }
+        Some(sample)
     }
 }
 
@@ -58,4 +83,8 @@ where
     fn total_duration(&self) -> Option<Duration> {
         self.input.total_duration()
     }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
 }
@@ -0,0 +1,30 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Reads a text file of unknown encoding: UTF-8, with or without a byte order
+/// mark, or else Latin-1, which is what older players and rippers tend to
+/// write playlists, cue sheets and lyrics in.
+pub fn read(path: &Path) -> io::Result<String> {
+    Ok(decode(&fs::read(path)?))
+}
+
+fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_latin1() {
+        assert_eq!(decode(b"\xEF\xBB\xBFcaf\xC3\xA9"), "café");
+        assert_eq!(decode(b"caf\xC3\xA9"), "café");
+        assert_eq!(decode(b"caf\xE9"), "café");
+    }
+}
@@ -0,0 +1,175 @@
+use rodio::Source;
+use rodio::source::SeekError;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Shared between a `TrackSource` on the audio thread and the player.
+pub struct TrackHandle {
+    started: AtomicBool,
+    cancelled: AtomicBool,
+    // Frames from the start of the file, so positions stay correct for cue
+    // tracks that start part way in.
+    frames: AtomicU64,
+    sample_rate: AtomicU32,
+    // Position in nanoseconds at which the source ends early, or `u64::MAX`.
+    stop_at: AtomicU64,
+}
+
+impl Default for TrackHandle {
+    fn default() -> Self {
+        Self {
+            started: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+            frames: AtomicU64::new(0),
+            sample_rate: AtomicU32::new(0),
+            stop_at: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+impl TrackHandle {
+    /// True once the output has pulled a sample from the track, which is when
+    /// the previous track has drained and this one becomes audible.
+    pub fn has_started(&self) -> bool {
+        self.started.load(Ordering::Relaxed)
+    }
+
+    /// Makes a track that is still waiting in the queue end immediately when
+    /// it is reached, so the sink moves straight on to whatever follows it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Position in the file of the last sample handed to the output.
+    pub fn position(&self) -> Duration {
+        let rate = self.sample_rate.load(Ordering::Relaxed);
+        if rate == 0 {
+            return Duration::ZERO;
+        }
+        let frames = self.frames.load(Ordering::Relaxed);
+        Duration::from_nanos((frames as u128 * 1_000_000_000 / rate as u128) as u64)
+    }
+
+    /// Ends the source once it reaches `position` in the file, for cue tracks
+    /// that are followed by something other than the next track in the file.
+    pub fn set_stop_at(&self, position: Option<Duration>) {
+        let nanos = position.map_or(u64::MAX, |p| p.as_nanos() as u64);
+        self.stop_at.store(nanos, Ordering::Relaxed);
+    }
+}
+
+pub struct TrackSource<I> {
+    input: I,
+    handle: Arc<TrackHandle>,
+    // Samples (not frames) from the start of the file.
+    samples: u64,
+}
+
+impl<I> TrackSource<I>
+where
+    I: Source,
+    I::Item: rodio::Sample,
+{
+    /// Wraps `input`, seeking it to `start` first when that isn't zero.
+    pub fn new(mut input: I, handle: Arc<TrackHandle>, start: Duration) -> Self {
+        if !start.is_zero() {
+            input.try_seek(start).ok();
+        }
+
+        let mut source = Self {
+            input,
+            handle,
+            samples: 0,
+        };
+        source.set_position(start);
+        source
+    }
+
+    fn channels_u64(&self) -> u64 {
+        self.input.channels().max(1) as u64
+    }
+
+    fn set_position(&mut self, position: Duration) {
+        let rate = self.input.sample_rate();
+        let frames = (position.as_nanos() * rate as u128 / 1_000_000_000) as u64;
+        self.samples = frames * self.channels_u64();
+        self.handle.sample_rate.store(rate, Ordering::Relaxed);
+        self.handle.frames.store(frames, Ordering::Relaxed);
+    }
+
+    /// Samples left before `stop_at`, rounded to whole frames so the next
+    /// source starts on the right channel.
+    fn remaining_before_stop(&self) -> Option<u64> {
+        let stop = self.handle.stop_at.load(Ordering::Relaxed);
+        if stop == u64::MAX {
+            return None;
+        }
+        let rate = self.input.sample_rate() as u128;
+        let stop_frames = (stop as u128 * rate / 1_000_000_000) as u64;
+        let stop_samples = stop_frames * self.channels_u64();
+        Some(stop_samples.saturating_sub(self.samples))
+    }
+}
+
+impl<I> Iterator for TrackSource<I>
+where
+    I: Source,
+    I::Item: rodio::Sample,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.handle.started.load(Ordering::Relaxed) {
+            if self.handle.cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            self.handle.started.store(true, Ordering::Relaxed);
+        }
+
+        if self.remaining_before_stop() == Some(0) {
+            return None;
+        }
+
+        let sample = self.input.next()?;
+        self.samples += 1;
+        if self.samples.is_multiple_of(self.channels_u64()) {
+            self.handle
+                .frames
+                .store(self.samples / self.channels_u64(), Ordering::Relaxed);
+        }
+        Some(sample)
+    }
+}
+
+impl<I> Source for TrackSource<I>
+where
+    I: Source,
+    I::Item: rodio::Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        match (self.input.current_frame_len(), self.remaining_before_stop()) {
+            (Some(len), Some(remaining)) => Some(len.min(remaining as usize)),
+            (None, Some(remaining)) => Some(remaining as usize),
+            (len, None) => len,
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.set_position(pos);
+        Ok(())
+    }
+}
@@ -1,179 +1,1235 @@
 use ratatui::{
     Frame,
-    layout::{Constraint, Layout, Rect},
+    layout::{Alignment, Constraint, Layout, Margin, Position, Rect},
     style::{Color, Modifier, Style},
+    symbols::Marker,
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, Paragraph, Sparkline},
+    widgets::{
+        Block, Borders, Gauge, Paragraph, Sparkline,
+        canvas::{self, Canvas, Points},
+    },
 };
-use std::path::Path;
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::cover_art::{CoverArt, GraphicsProtocol};
+use crate::gradient::Gradient;
+use crate::lyrics::Lyrics;
+use crate::metadata::TrackMetadata;
 use crate::player::PlaybackState;
-use crate::spectrum::SpectrumAnalyzer;
-use crate::waveform::WaveformData;
+use crate::playlist::RepeatMode;
+use crate::spectrum::{Bar, SPECTROGRAM_BANDS, SpectrumAnalyzer};
+use crate::waveform::{WaveformBar, WaveformData, WaveformStyle};
+
+/// Left/right correlation below which the per-channel waveform marks a
+/// stretch as out of phase.
+const OUT_OF_PHASE: f32 = -0.5;
+
+/// Upper bound for the waveform zoom before rendering limits it further.
+const MAX_ZOOM: u32 = 16;
+
+/// Frames across the oscilloscope, about 23 ms at 44.1 kHz.
+const SCOPE_FRAMES: usize = 1024;
+
+/// Frames plotted at once by the vectorscope.
+const VECTORSCOPE_FRAMES: usize = 2048;
+
+/// How long a status message stays in the title bar.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What the visualization panel shows while the analyzer is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visualizer {
+    Waveform,
+    Spectrum,
+    Spectrogram,
+    Oscilloscope,
+    Vectorscope,
+}
+
+impl Visualizer {
+    pub fn next(self) -> Self {
+        match self {
+            Visualizer::Waveform => Visualizer::Spectrum,
+            Visualizer::Spectrum => Visualizer::Spectrogram,
+            Visualizer::Spectrogram => Visualizer::Oscilloscope,
+            Visualizer::Oscilloscope => Visualizer::Vectorscope,
+            Visualizer::Vectorscope => Visualizer::Waveform,
+        }
+    }
+}
+
+/// How the spectrum analyzer draws its bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectrumStyle {
+    /// Solid bars, in eighths of a cell.
+    Blocks,
+    /// Thin bars of Braille dots, twice as many across.
+    Braille,
+}
+
+impl SpectrumStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "blocks" => Some(SpectrumStyle::Blocks),
+            "braille" => Some(SpectrumStyle::Braille),
+            _ => None,
+        }
+    }
+
+    pub fn toggle(self) -> Self {
+        match self {
+            SpectrumStyle::Blocks => SpectrumStyle::Braille,
+            SpectrumStyle::Braille => SpectrumStyle::Blocks,
+        }
+    }
+}
 
 pub struct UIState {
-    pub filename: String,
+    // Shown when the file has no title tag.
+    pub title: String,
+    pub metadata: TrackMetadata,
+    pub show_info: bool,
+    pub show_lyrics: bool,
+    // Only the title and visualization are shown.
+    pub fullscreen: bool,
+    // Waveform drawn per channel instead of mixed.
+    pub split_channels: bool,
+    // The waveform shows 1/2^zoom of the track at a time.
+    waveform_zoom: u32,
+    // Keep the playhead in the middle of a zoomed waveform and scroll the
+    // waveform under it, rather than turning pages.
+    pub follow_playhead: bool,
+    // First line shown of unsynchronized lyrics.
+    lyrics_scroll: usize,
+    pub track_index: usize,
+    // Where the track comes in the play order, for the counter.
+    pub track_position: usize,
+    pub track_count: usize,
     pub position: Duration,
     pub duration: Duration,
     pub volume: f32,
     pub state: PlaybackState,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
     pub waveform: WaveformData,
+    pub waveform_style: WaveformStyle,
+    // Set once a visualizer other than the waveform has been shown.
     pub spectrum: Option<Arc<Mutex<SpectrumAnalyzer>>>,
+    pub visualizer: Visualizer,
+    pub spectrum_style: SpectrumStyle,
+    // Frequency and dB scales around the spectrum.
+    pub show_axes: bool,
+    // Plot column whose frequency and level are read out, once moved.
+    spectrum_cursor: Option<usize>,
+    pub gradient: Gradient,
+    // Vectorscope plots mid against side rather than left against right.
+    pub mid_side: bool,
+    pub cover: CoverArt,
+    message: Option<(String, Instant)>,
+    // Where things ended up in the last render, for the mouse.
+    click_areas: ClickAreas,
+}
+
+/// Screen areas that respond to the mouse, as of the last render.
+#[derive(Default)]
+struct ClickAreas {
+    // The waveform plot and the part of the track it showed, as
+    // (start, span) fractions. None while another visualizer is showing.
+    waveform: Option<(Rect, f64, f64)>,
+    // The spectrum's bars, while they're showing.
+    spectrum: Option<Rect>,
+    progress: Rect,
+    volume: Rect,
+    // Control hints and the key each stands for.
+    hints: Vec<(Rect, char)>,
+}
+
+/// What a click or scroll at some spot on the screen means.
+pub enum ClickTarget {
+    /// Seek to this fraction of the track.
+    Seek(f64),
+    Volume,
+    /// Act as if this key was pressed.
+    Key(char),
+    /// This column of the spectrum's bars.
+    Spectrum(usize),
 }
 
 impl UIState {
-    pub fn new<P: AsRef<Path>>(
-        path: P,
+    pub fn new(
+        title: String,
+        metadata: TrackMetadata,
         duration: Duration,
         waveform: WaveformData,
+        waveform_style: WaveformStyle,
         spectrum: Option<Arc<Mutex<SpectrumAnalyzer>>>,
+        cover_protocol: GraphicsProtocol,
     ) -> Self {
-        let filename = path
-            .as_ref()
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("Unknown")
-            .to_string();
+        let mut cover = CoverArt::new(cover_protocol);
+        cover.set_source(metadata.cover.as_ref());
 
         Self {
-            filename,
+            title,
+            metadata,
+            show_info: false,
+            show_lyrics: true,
+            fullscreen: false,
+            split_channels: false,
+            waveform_zoom: 0,
+            follow_playhead: false,
+            lyrics_scroll: 0,
+            track_index: 0,
+            track_position: 0,
+            track_count: 1,
             position: Duration::from_secs(0),
             duration,
             volume: 1.0,
             state: PlaybackState::Paused,
+            shuffle: false,
+            repeat: RepeatMode::Off,
             waveform,
+            waveform_style,
             spectrum,
+            visualizer: Visualizer::Waveform,
+            spectrum_style: SpectrumStyle::Blocks,
+            show_axes: true,
+            spectrum_cursor: None,
+            gradient: Gradient::Viridis,
+            mid_side: false,
+            cover,
+            message: None,
+            click_areas: ClickAreas::default(),
         }
     }
+
+    pub fn set_track(
+        &mut self,
+        title: String,
+        metadata: TrackMetadata,
+        duration: Duration,
+        waveform: WaveformData,
+    ) {
+        self.cover.set_source(metadata.cover.as_ref());
+        self.title = title;
+        self.metadata = metadata;
+        self.lyrics_scroll = 0;
+        self.position = Duration::from_secs(0);
+        self.duration = duration;
+        self.waveform = waveform;
+    }
+
+    /// Scrolls unsynchronized lyrics; synced lyrics follow playback instead.
+    pub fn scroll_lyrics(&mut self, lines: isize) {
+        self.lyrics_scroll = self.lyrics_scroll.saturating_add_signed(lines);
+    }
+
+    /// Zooms the waveform in (positive) or out by factors of two. How far in
+    /// it goes is limited when rendering, once the width is known.
+    pub fn zoom_waveform(&mut self, steps: i32) {
+        self.waveform_zoom = self
+            .waveform_zoom
+            .saturating_add_signed(steps)
+            .min(MAX_ZOOM);
+    }
+
+    /// What is at `column`, `row` on the screen.
+    pub fn click_target(&self, column: u16, row: u16) -> Option<ClickTarget> {
+        let position = Position::new(column, row);
+        // How far across `area` the position is, from 0.0 to 1.0.
+        let across =
+            |area: Rect| (column.saturating_sub(area.x) as f64 / area.width.max(1) as f64).min(1.0);
+        let areas = &self.click_areas;
+
+        if let Some(area) = areas.spectrum
+            && area.contains(position)
+        {
+            return Some(ClickTarget::Spectrum((column - area.x) as usize));
+        }
+        if let Some((area, start, span)) = areas.waveform
+            && area.contains(position)
+        {
+            return Some(ClickTarget::Seek(
+                (start + across(area) * span).clamp(0.0, 1.0),
+            ));
+        }
+        if areas.progress.contains(position) {
+            return Some(ClickTarget::Seek(across(areas.progress)));
+        }
+        if areas.volume.contains(position) {
+            return Some(ClickTarget::Volume);
+        }
+        areas
+            .hints
+            .iter()
+            .find(|(area, _)| area.contains(position))
+            .map(|&(_, key)| ClickTarget::Key(key))
+    }
+
+    /// Moves the spectrum cursor by `columns`, starting from the middle the
+    /// first time.
+    pub fn move_spectrum_cursor(&mut self, columns: isize) {
+        let width = self
+            .click_areas
+            .spectrum
+            .map_or(0, |area| area.width as usize);
+        let current = self.spectrum_cursor.unwrap_or(width / 2);
+        let moved = current.saturating_add_signed(columns);
+        self.spectrum_cursor = Some(moved.min(width.saturating_sub(1)));
+    }
+
+    pub fn set_spectrum_cursor(&mut self, column: usize) {
+        self.spectrum_cursor = Some(column);
+    }
+
+    /// Shows a short status message in the title bar for a few seconds.
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
+    }
+
+    fn current_message(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .filter(|(_, shown)| shown.elapsed() < MESSAGE_TIMEOUT)
+            .map(|(message, _)| message.as_str())
+    }
 }
 
-pub fn render(frame: &mut Frame, state: &UIState) {
+pub fn render(frame: &mut Frame, state: &mut UIState) {
     let area = frame.area();
 
-    let viz_height = if state.spectrum.is_some() {
-        area.height.saturating_sub(12).max(10)
-    } else if state.waveform.enhanced {
+    let title_height = if state.show_info { 4 } else { 3 };
+
+    if state.fullscreen {
+        let [title, visualization] =
+            Layout::vertical([Constraint::Length(title_height), Constraint::Min(0)]).areas(area);
+        render_title(frame, title, state);
+        render_visualization(frame, visualization, state);
+        state.click_areas.progress = Rect::default();
+        state.click_areas.volume = Rect::default();
+        state.click_areas.hints.clear();
+        return;
+    }
+
+    let show_lyrics = state.show_lyrics && state.metadata.lyrics.is_some();
+
+    let viz_height = if state.visualizer != Visualizer::Waveform {
+        let available = area.height.saturating_sub(9 + title_height);
+        // Leave a third of the space below for the lyrics.
+        if show_lyrics {
+            (available * 2 / 3).max(10)
+        } else {
+            available.max(10)
+        }
+    } else if state.split_channels && state.waveform.channels() > 2 {
+        // Two rows per lane.
+        (2 + 2 * state.waveform.channels() as u16).max(9)
+    } else if state.waveform_style == WaveformStyle::Enhanced || state.split_channels {
         9
     } else {
         5
     };
 
     let chunks = Layout::vertical([
-        Constraint::Length(3),          // Title
-        Constraint::Length(viz_height), // Waveform/Spectrum
-        Constraint::Length(3),          // Progress
-        Constraint::Length(3),          // Volume
-        Constraint::Min(0),             // Spacer
-        Constraint::Length(3),          // Controls
+        Constraint::Length(title_height + viz_height), // Cover, title and waveform/spectrum
+        Constraint::Length(3),                         // Progress
+        Constraint::Length(3),                         // Volume
+        Constraint::Min(0),                            // Lyrics or spacer
+        Constraint::Length(3),                         // Controls
     ])
     .split(area);
 
-    render_title(frame, chunks[0], state);
-    render_visualization(frame, chunks[1], state);
-    render_progress(frame, chunks[2], state);
-    render_volume(frame, chunks[3], state);
-    render_controls(frame, chunks[5]);
+    // Roughly square, as cells are about twice as tall as they are wide.
+    let cover_width = if state.cover.is_visible() {
+        (chunks[0].height * 2).min(area.width / 3)
+    } else {
+        0
+    };
+    let top =
+        Layout::horizontal([Constraint::Length(cover_width), Constraint::Min(0)]).split(chunks[0]);
+    let main = Layout::vertical([
+        Constraint::Length(title_height), // Title
+        Constraint::Min(0),               // Waveform/Spectrum
+    ])
+    .split(top[1]);
+
+    if cover_width > 0 {
+        render_cover(frame, top[0], &mut state.cover);
+    }
+    render_title(frame, main[0], state);
+    render_visualization(frame, main[1], state);
+    render_progress(frame, chunks[1], state);
+    render_volume(frame, chunks[2], state);
+    state.click_areas.progress = chunks[1].inner(Margin::new(1, 1));
+    state.click_areas.volume = chunks[2];
+    if show_lyrics && chunks[3].height >= 3 {
+        render_lyrics(frame, chunks[3], state);
+    }
+    state.click_areas.hints = render_controls(frame, chunks[4]);
+}
+
+fn render_lyrics(frame: &mut Frame, area: Rect, state: &mut UIState) {
+    let block = Block::default().borders(Borders::ALL).title("Lyrics");
+    let inner = block.inner(area);
+    let height = inner.height as usize;
+
+    let paragraph = match &state.metadata.lyrics {
+        Some(Lyrics::Synced(lines)) => {
+            let current = lines
+                .partition_point(|line| line.time <= state.position)
+                .checked_sub(1);
+            // Keep the current line in the middle of the panel.
+            let first = current.map_or(0, |i| i.saturating_sub(height / 2));
+
+            let text: Vec<Line> = lines
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(height)
+                .map(|(i, line)| {
+                    if Some(i) != current {
+                        let color = if current.is_some_and(|c| i < c) {
+                            Color::DarkGray
+                        } else {
+                            Color::Gray
+                        };
+                        return Line::styled(line.text.as_str(), Style::default().fg(color));
+                    }
+
+                    let sung = Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD);
+                    if line.words.is_empty() {
+                        return Line::styled(line.text.as_str(), sung);
+                    }
+                    let upcoming = Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD);
+                    Line::from(
+                        line.words
+                            .iter()
+                            .map(|(time, word)| {
+                                let style = if *time <= state.position {
+                                    sung
+                                } else {
+                                    upcoming
+                                };
+                                Span::styled(word.as_str(), style)
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect();
+
+            Paragraph::new(text).alignment(Alignment::Center)
+        }
+        Some(Lyrics::Plain(lines)) => {
+            state.lyrics_scroll = state.lyrics_scroll.min(lines.len().saturating_sub(height));
+            let text: Vec<Line> = lines
+                .iter()
+                .skip(state.lyrics_scroll)
+                .take(height)
+                .map(|line| Line::raw(line.as_str()))
+                .collect();
+
+            Paragraph::new(text)
+        }
+        None => return,
+    };
+
+    frame.render_widget(paragraph.block(block), area);
+}
+
+fn render_cover(frame: &mut Frame, area: Rect, cover: &mut CoverArt) {
+    let block = Block::default().borders(Borders::ALL).title("Cover");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    cover.render(frame, inner);
 }
 
-fn render_visualization(frame: &mut Frame, area: Rect, state: &UIState) {
-    if let Some(spectrum) = &state.spectrum {
-        render_spectrum_bars(frame, area, state, spectrum);
-    } else if state.waveform.enhanced {
-        render_enhanced_waveform(frame, area, state);
+fn render_visualization(frame: &mut Frame, area: Rect, state: &mut UIState) {
+    if state.visualizer != Visualizer::Waveform {
+        let mut bars = None;
+        if let Some(spectrum) = &state.spectrum {
+            match state.visualizer {
+                Visualizer::Spectrum => {
+                    bars = Some(render_spectrum_bars(frame, area, state, spectrum));
+                }
+                Visualizer::Spectrogram => render_spectrogram(frame, area, state, spectrum),
+                Visualizer::Oscilloscope => render_oscilloscope(frame, area, state, spectrum),
+                Visualizer::Vectorscope => render_vectorscope(frame, area, state, spectrum),
+                Visualizer::Waveform => {}
+            }
+        }
+        state.click_areas.spectrum = bars;
+        state.click_areas.waveform = None;
+        return;
+    }
+    state.click_areas.spectrum = None;
+
+    let view = WaveformView::new(state, area.width.saturating_sub(2) as usize);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(view.title(state.duration, area.width.saturating_sub(2)));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    // The overview strip takes the bottom row while zoomed in.
+    let (plot, overview) = if view.zoom > 0 && inner.height > 2 {
+        let [plot, overview] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+        (plot, Some(overview))
+    } else {
+        (inner, None)
+    };
+
+    if state.waveform_style == WaveformStyle::Enhanced || state.split_channels {
+        // The sparkline has no room for more than one channel.
+        render_enhanced_waveform(frame, plot, state, &view);
     } else {
-        render_simple_waveform(frame, area, state);
+        render_simple_waveform(frame, plot, state, &view);
+    }
+    if let Some(overview) = overview {
+        render_overview(frame, overview, state, &view);
+    }
+    state.click_areas.waveform = Some((plot, view.start, view.span));
+}
+
+/// The part of the track the waveform panel shows.
+struct WaveformView {
+    zoom: u32,
+    /// Where the view starts, as a fraction of the track. Following the
+    /// playhead near either end takes it below 0 or the end past 1.
+    start: f64,
+    /// How much of the track the view spans, as a fraction.
+    span: f64,
+    /// The playhead, as a fraction of the track.
+    playhead: f64,
+    follow: bool,
+}
+
+impl WaveformView {
+    /// Works out the view for `width` columns, first limiting the zoom to a
+    /// stored peak per column.
+    fn new(state: &mut UIState, width: usize) -> Self {
+        let max_zoom = (state.waveform.peak_count() / width.max(1)).max(1).ilog2();
+        state.waveform_zoom = state.waveform_zoom.min(max_zoom);
+
+        let zoom = state.waveform_zoom;
+        let span = 0.5f64.powi(zoom as i32);
+        let playhead = if state.duration.is_zero() {
+            0.0
+        } else {
+            (state.position.as_secs_f64() / state.duration.as_secs_f64()).min(1.0)
+        };
+        let start = if state.follow_playhead {
+            playhead - span / 2.0
+        } else {
+            // Pages along with playback: a new page once the playhead runs
+            // off the end of the current one.
+            (playhead / span).floor().min((1.0 / span) - 1.0) * span
+        };
+
+        Self {
+            zoom,
+            start,
+            span,
+            playhead,
+            follow: state.follow_playhead,
+        }
+    }
+
+    fn title(&self, duration: Duration, width: u16) -> String {
+        let mut title = String::from("Waveform");
+        if self.zoom > 0 {
+            let per_column = duration.as_secs_f64() * self.span / width.max(1) as f64;
+            title.push_str(&format!(" {}× · {:.2}s/col", 1u32 << self.zoom, per_column));
+        }
+        if self.follow {
+            title.push_str(" · following");
+        }
+        title
+    }
+
+    /// The playhead's column out of `width`.
+    fn cursor(&self, width: usize) -> usize {
+        ((self.playhead - self.start) / self.span * width as f64) as usize
+    }
+
+    /// The columns out of `width` that fall within the track, and the part
+    /// of the track they show.
+    fn visible(&self, width: usize) -> (Range<usize>, f64, f64) {
+        let column = |fraction: f64| (fraction - self.start) / self.span * width as f64;
+        let first = (column(0.0).ceil().max(0.0) as usize).min(width);
+        let last = (column(1.0).floor().max(0.0) as usize).clamp(first, width);
+        let fraction = |column: usize| self.start + column as f64 * self.span / width as f64;
+        (first..last, fraction(first), fraction(last))
+    }
+
+    /// The bars for `width` columns, silent where the view runs past either
+    /// end of the track.
+    fn bars(&self, waveform: &WaveformData, width: usize) -> Vec<WaveformBar> {
+        let (columns, from, to) = self.visible(width);
+        let mut bars = vec![WaveformBar::default(); width];
+        bars[columns.clone()].copy_from_slice(&waveform.window(from, to, columns.len()));
+        bars
+    }
+
+    /// Like `bars`, for each channel.
+    fn channel_bars(&self, waveform: &WaveformData, width: usize) -> Vec<Vec<WaveformBar>> {
+        let (columns, from, to) = self.visible(width);
+        waveform
+            .window_channels(from, to, columns.len())
+            .into_iter()
+            .map(|visible| {
+                let mut bars = vec![WaveformBar::default(); width];
+                bars[columns.clone()].copy_from_slice(&visible);
+                bars
+            })
+            .collect()
     }
 }
 
+/// Eighths of a cell, from one up to full.
+const EIGHTHS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+/// Braille dot bits for each of the four rows, top down, in the left and
+/// right columns of a cell.
+const BRAILLE_LEFT: [u32; 4] = [0x01, 0x02, 0x04, 0x40];
+const BRAILLE_RIGHT: [u32; 4] = [0x08, 0x10, 0x20, 0x80];
+
+/// Frequencies marked along the bottom of the spectrum, and their labels.
+const FREQUENCY_TICKS: [(f32, &str); 9] = [
+    (50.0, "50"),
+    (100.0, "100"),
+    (200.0, "200"),
+    (500.0, "500"),
+    (1000.0, "1k"),
+    (2000.0, "2k"),
+    (5000.0, "5k"),
+    (10000.0, "10k"),
+    (20000.0, "20k"),
+];
+
+/// Width of the dB scale to the left of the spectrum, tick included.
+const DB_SCALE_WIDTH: u16 = 5;
+
+/// Draws the bars, and the axes if they're on. Returns the area the bars
+/// took up, for the mouse.
 fn render_spectrum_bars(
     frame: &mut Frame,
     area: Rect,
     state: &UIState,
     spectrum: &Arc<Mutex<SpectrumAnalyzer>>,
+) -> Rect {
+    let braille = state.spectrum_style == SpectrumStyle::Braille;
+    let inner = area.inner(Margin::new(1, 1));
+    let (plot, scale, axis) =
+        if state.show_axes && inner.width > DB_SCALE_WIDTH * 3 && inner.height > 4 {
+            let [rest, axis] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+            let [scale, plot] =
+                Layout::horizontal([Constraint::Length(DB_SCALE_WIDTH), Constraint::Min(0)])
+                    .areas(rest);
+            let axis = Rect::new(plot.x, axis.y, plot.width, 1);
+            (plot, Some(scale), Some(axis))
+        } else {
+            (inner, None, None)
+        };
+
+    let mut analyzer = spectrum.lock().unwrap();
+    analyzer.set_split(state.split_channels);
+    // Boosted bars would read too loud against the scale.
+    analyzer.set_bass_boost(scale.is_none());
+    analyzer.update();
+    let split = analyzer.is_split();
+
+    // Braille fits two bars in a cell.
+    let width = plot.width as usize;
+    let slots = if braille { width * 2 } else { width };
+
+    // Left to right. Split, the left channel runs from high to low so the
+    // two basses meet in the middle.
+    let columns: Vec<Slot> = match analyzer.bars() {
+        [left, right] => {
+            let mut columns = fit_bars(left, slots / 2);
+            columns.reverse();
+            columns.extend(fit_bars(right, slots - slots / 2));
+            columns
+        }
+        sets => fit_bars(&sets[0], slots),
+    };
+
+    let half = width / 2;
+    let frequencies = analyzer.bar_frequencies();
+
+    // The frequency and level of the bar under the cursor, and which
+    // channel it's in.
+    let readout = state
+        .spectrum_cursor
+        .filter(|_| width > 0)
+        .and_then(|cursor| {
+            let x = cursor.min(width - 1);
+            let slot = if braille {
+                let (a, b) = (columns[x * 2], columns[x * 2 + 1]);
+                if b.bar.level > a.bar.level { b } else { a }
+            } else {
+                columns[x]
+            };
+            let channel = match (split, x < half) {
+                (false, _) => "",
+                (true, true) => "L ",
+                (true, false) => "R ",
+            };
+            let freq = frequencies.get(slot.index)?;
+            let db = analyzer.bar_db(slot.index, slot.bar.level);
+            // An empty bar only says the level is somewhere below the floor.
+            let below = if slot.bar.level > 0.0 { "" } else { "< " };
+            Some(format!(
+                " · {}{} {}{:.0} dB",
+                channel,
+                format_frequency(*freq),
+                below,
+                db
+            ))
+        });
+
+    let (db_floor, db_ceiling) = analyzer.db_range();
+
+    // Where a fraction of the way across the bars lands, in cells across
+    // the plot, for each channel shown.
+    let placements = |fraction: f32| -> Vec<f32> {
+        if split {
+            vec![
+                half as f32 * (1.0 - fraction),
+                half as f32 + (width - half) as f32 * fraction,
+            ]
+        } else {
+            vec![width as f32 * fraction]
+        }
+    };
+
+    let mut title = vec![Span::raw(match (split, braille) {
+        (false, false) => "Spectrum Analyzer",
+        (false, true) => "Spectrum Analyzer · braille",
+        (true, false) => "Spectrum Analyzer · L | R",
+        (true, true) => "Spectrum Analyzer · L | R · braille",
+    })];
+    if let Some(readout) = readout {
+        title.push(Span::styled(readout, Style::default().fg(Color::White)));
+    }
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(Line::from(title)),
+        area,
+    );
+
+    if let Some(scale) = scale {
+        render_db_scale(frame, scale, db_floor, db_ceiling);
+    }
+    if let Some(axis) = axis {
+        let mut next_free = 0;
+        let mut labels = Vec::new();
+        for (freq, label) in FREQUENCY_TICKS {
+            let Some(position) = bar_position(frequencies, freq) else {
+                continue;
+            };
+            for x in placements(position / frequencies.len() as f32) {
+                labels.push((x, label));
+            }
+        }
+        labels.sort_by(|a, b| a.0.total_cmp(&b.0));
+        // Centred on their frequency, leaving out any that would run into
+        // the one before or off the end.
+        for (x, label) in labels {
+            let start = (x - label.len() as f32 / 2.0).round().max(0.0) as usize;
+            if start < next_free || start + label.len() > width {
+                continue;
+            }
+            frame.buffer_mut().set_string(
+                axis.x + start as u16,
+                axis.y,
+                label,
+                Style::default().fg(Color::DarkGray),
+            );
+            next_free = start + label.len() + 1;
+        }
+    }
+
+    let waveform_color = match state.state {
+        PlaybackState::Playing => Color::Cyan,
+        PlaybackState::Paused => Color::Yellow,
+    };
+
+    if braille {
+        render_braille_bars(frame, plot, &columns, waveform_color);
+    } else {
+        render_block_bars(frame, plot, &columns, waveform_color);
+    }
+
+    if let Some(cursor) = state.spectrum_cursor.filter(|_| width > 0) {
+        let x = plot.x + cursor.min(width - 1) as u16;
+        for y in plot.top()..plot.bottom() {
+            frame.buffer_mut()[(x, y)].set_bg(Color::DarkGray);
+        }
+    }
+
+    plot
+}
+
+/// Labels the plot's rows with their level, at a step that leaves room
+/// between labels.
+fn render_db_scale(frame: &mut Frame, area: Rect, floor: f32, ceiling: f32) {
+    let height = area.height as usize;
+    let range = ceiling - floor;
+    let step = [3.0, 6.0, 10.0, 20.0, 30.0, 40.0, 60.0]
+        .into_iter()
+        .find(|step| step / range * height as f32 >= 2.0)
+        .unwrap_or(range);
+
+    let mut db = (ceiling / step).floor() * step;
+    while db >= floor {
+        let fraction = (db - floor) / range;
+        let y = area.y + ((1.0 - fraction) * (height - 1) as f32).round() as u16;
+        frame.buffer_mut().set_string(
+            area.x,
+            y,
+            format!("{:>4}┤", db as i32),
+            Style::default().fg(Color::DarkGray),
+        );
+        db -= step;
+    }
+}
+
+/// Where `freq` falls among bars with centre `frequencies`, counting in
+/// bars from the left edge of the first. Bars sharing a bin leave a gap
+/// that nothing lands in.
+fn bar_position(frequencies: &[f32], freq: f32) -> Option<f32> {
+    let last = frequencies.len().checked_sub(1)?;
+    if freq < frequencies[0] || freq > frequencies[last] {
+        return None;
+    }
+    // The last bar at or below `freq`, so the next one is above it.
+    let i = frequencies.partition_point(|&f| f <= freq) - 1;
+    if i == last {
+        return Some(last as f32 + 0.5);
+    }
+    let (low, high) = (frequencies[i], frequencies[i + 1]);
+    Some(i as f32 + 0.5 + (freq / low).ln() / (high / low).ln())
+}
+
+fn format_frequency(freq: f32) -> String {
+    if freq < 1000.0 {
+        format!("{:.0} Hz", freq)
+    } else {
+        format!("{:.1} kHz", freq / 1000.0)
+    }
+}
+
+/// Draws one bar per cell, in eighths of a cell, with a cap above each.
+fn render_block_bars(frame: &mut Frame, area: Rect, columns: &[Slot], base: Color) {
+    let height = area.height as usize;
+    for (x, &Slot { bar, hue, .. }) in columns.iter().enumerate() {
+        let eighths = ((bar.level * (height * 8) as f32).round() as usize).min(height * 8);
+        let cells = eighths.div_ceil(8);
+
+        for h in 0..cells {
+            let glyph = if h < eighths / 8 {
+                EIGHTHS[7]
+            } else {
+                EIGHTHS[eighths % 8 - 1]
+            };
+            let cell =
+                &mut frame.buffer_mut()[(area.x + x as u16, area.y + (height - h - 1) as u16)];
+            cell.set_symbol(glyph);
+            cell.set_fg(bar_color(h as f32 / cells as f32, hue, base));
+        }
+
+        // The cap rests on top of the bar, or floats above it while it's
+        // held or falling. A full bar has no room left for one.
+        let cap = ((bar.peak * height as f32) as usize).max(cells);
+        if cap < height {
+            let cell =
+                &mut frame.buffer_mut()[(area.x + x as u16, area.y + (height - cap - 1) as u16)];
+            cell.set_symbol("▁");
+            cell.set_fg(Color::White);
+        }
+    }
+}
+
+/// A column of the spectrum, or half of one in Braille.
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    bar: Bar,
+    /// The analyzer's bar whose level `bar` shows.
+    index: usize,
+    /// Where the slot is in the frequency range, for colouring.
+    hue: f32,
+}
+
+/// Spreads `bars` across `slots` columns: wide bars when there's room, or
+/// the loudest of each group when there isn't, so none get cut off.
+fn fit_bars(bars: &[Bar], slots: usize) -> Vec<Slot> {
+    let count = bars.len();
+    if count == 0 {
+        return vec![Slot::default(); slots];
+    }
+    (0..slots)
+        .map(|slot| {
+            let first = slot * count / slots;
+            let end = ((slot + 1) * count / slots).max(first + 1);
+            let mut fitted = Slot {
+                bar: bars[first],
+                index: first,
+                hue: first as f32 / count as f32,
+            };
+            for (index, bar) in bars.iter().enumerate().take(end).skip(first + 1) {
+                if bar.level > fitted.bar.level {
+                    fitted.bar.level = bar.level;
+                    fitted.index = index;
+                }
+                fitted.bar.peak = fitted.bar.peak.max(bar.peak);
+            }
+            fitted
+        })
+        .collect()
+}
+
+/// Colours the part of a bar `intensity` of the way up it, with the top
+/// taking on a colour for where the bar is in the frequency range.
+fn bar_color(intensity: f32, hue: f32, base: Color) -> Color {
+    if intensity > 0.8 {
+        Color::Red
+    } else if intensity > 0.5 {
+        if hue < 0.33 {
+            Color::Magenta
+        } else if hue < 0.66 {
+            base
+        } else {
+            Color::Green
+        }
+    } else {
+        base
+    }
+}
+
+/// Draws two bars to a cell with Braille dots, four dots to a row. Caps are
+/// single dots, and show white in a cell of their own.
+fn render_braille_bars(frame: &mut Frame, area: Rect, columns: &[Slot], base: Color) {
+    let height = area.height as usize;
+    let dots = height * 4;
+    // (dots filled, dot the cap is on) for each bar.
+    let levels: Vec<(usize, usize)> = columns
+        .iter()
+        .map(|Slot { bar, .. }| {
+            let filled = ((bar.level * dots as f32).round() as usize).min(dots);
+            (filled, ((bar.peak * dots as f32) as usize).max(filled))
+        })
+        .collect();
+
+    for (x, pair) in levels.chunks(2).enumerate() {
+        let tallest = pair.iter().map(|&(filled, _)| filled).max().unwrap_or(0);
+        let hue = columns[x * 2].hue;
+        for y in 0..height {
+            let bottom = (height - 1 - y) * 4;
+            let (mut bar_bits, mut cap_bits) = (0, 0);
+            for (&(filled, cap), bits) in pair.iter().zip([BRAILLE_LEFT, BRAILLE_RIGHT]) {
+                for (row, bit) in bits.iter().enumerate() {
+                    let dot = bottom + 3 - row;
+                    if dot < filled {
+                        bar_bits |= bit;
+                    } else if dot == cap {
+                        cap_bits |= bit;
+                    }
+                }
+            }
+            if bar_bits | cap_bits == 0 {
+                continue;
+            }
+
+            let color = if bar_bits == 0 {
+                Color::White
+            } else {
+                bar_color(bottom as f32 / tallest as f32, hue, base)
+            };
+            let glyph = char::from_u32(0x2800 + (bar_bits | cap_bits)).unwrap_or(' ');
+            let cell = &mut frame.buffer_mut()[(area.x + x as u16, area.y + y as u16)];
+            cell.set_char(glyph);
+            cell.set_fg(color);
+        }
+    }
+}
+
+/// Draws the spectrogram with the newest column on the right. Each cell is
+/// split in two with `▀`, so it holds two frequency rows.
+fn render_spectrogram(
+    frame: &mut Frame,
+    area: Rect,
+    state: &UIState,
+    spectrum: &Arc<Mutex<SpectrumAnalyzer>>,
 ) {
     let block = Block::default()
         .borders(Borders::ALL)
-        .title("Spectrum Analyzer");
+        .title(format!("Spectrogram · {}", state.gradient.name()));
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
     let mut analyzer = spectrum.lock().unwrap();
-    analyzer.update();
-    let bars = analyzer.bars();
-    let num_bars = analyzer.num_bars();
+    analyzer.update_spectrogram();
+    let history = analyzer.spectrogram();
 
     let width = inner.width as usize;
     let height = inner.height as usize;
-    let bar_width = (width / num_bars).max(1);
+    let rows = height * 2;
+    // Row 0 is the lowest frequency; each takes the band at its middle.
+    let band = |row: usize| (row * 2 + 1) * SPECTROGRAM_BANDS / (rows * 2);
+    let quiet = state.gradient.color(0.0);
+
+    for x in 0..width {
+        // Columns from before the history starts stay quiet.
+        let column = (history.len() + x).checked_sub(width).map(|i| &history[i]);
+        for y in 0..height {
+            let lower = (height - 1 - y) * 2;
+            let (top, bottom) = match column {
+                Some(levels) => (
+                    state.gradient.color(levels[band(lower + 1)]),
+                    state.gradient.color(levels[band(lower)]),
+                ),
+                None => (quiet, quiet),
+            };
+            let cell = &mut frame.buffer_mut()[(inner.x + x as u16, inner.y + y as u16)];
+            cell.set_symbol("▀");
+            cell.set_fg(top);
+            cell.set_bg(bottom);
+        }
+    }
+}
+
+/// The most recent `frames` frames from the analyzer's tap on the audio, and
+/// how many channels they have.
+fn recent_samples(
+    spectrum: &Arc<Mutex<SpectrumAnalyzer>>,
+    frames: usize,
+) -> Option<(Vec<f32>, usize)> {
+    let ring = spectrum.lock().unwrap().sample_ring();
+    let channels = ring.channels();
+    let mut samples = vec![0.0; frames * channels];
+    ring.snapshot(&mut samples).then_some((samples, channels))
+}
+
+/// A time-domain trace of the last few milliseconds, lined up on a rising
+/// zero crossing so steady tones stand still. Split, it draws the left and
+/// right channels over each other.
+fn render_oscilloscope(
+    frame: &mut Frame,
+    area: Rect,
+    state: &UIState,
+    spectrum: &Arc<Mutex<SpectrumAnalyzer>>,
+) {
+    let samples = recent_samples(spectrum, SCOPE_FRAMES * 2);
+    let channels = samples.as_ref().map_or(1, |(_, channels)| *channels);
+    let split = state.split_channels && channels > 1;
 
     let waveform_color = match state.state {
         PlaybackState::Playing => Color::Cyan,
         PlaybackState::Paused => Color::Yellow,
     };
-
-    for (i, &amplitude) in bars.iter().enumerate() {
-        let x_pos = i * bar_width;
-        if x_pos >= width {
-            break;
+    let traces: Vec<(Vec<f32>, Color)> = match &samples {
+        None => Vec::new(),
+        Some((samples, channels)) => {
+            let frames = samples.chunks_exact(*channels);
+            if split {
+                vec![
+                    (
+                        frames.clone().map(|frame| frame[0]).collect(),
+                        waveform_color,
+                    ),
+                    (frames.map(|frame| frame[1]).collect(), Color::Magenta),
+                ]
+            } else {
+                let mix = frames.map(|frame| frame.iter().sum::<f32>() / *channels as f32);
+                vec![(mix.collect(), waveform_color)]
+            }
         }
+    };
 
-        let bar_height = (amplitude * height as f32 * 0.5) as usize;
-        let bar_height = bar_height.min(height);
+    // The latest rising zero crossing that leaves a full trace after it, or
+    // just the latest samples when there isn't one.
+    let start = traces.first().map_or(0, |(trace, _)| {
+        (1..=SCOPE_FRAMES)
+            .rev()
+            .find(|&i| trace[i - 1] < 0.0 && trace[i] >= 0.0)
+            .unwrap_or(SCOPE_FRAMES)
+    });
 
-        for h in 0..bar_height {
-            let y = height.saturating_sub(h + 1);
+    let sample_rate = spectrum.lock().unwrap().sample_ring().sample_rate();
+    let millis = SCOPE_FRAMES as f64 * 1000.0 / sample_rate as f64;
+    let title = format!("Oscilloscope · {:.0} ms", millis);
+    let dots = area.width.saturating_sub(2) as usize * 2;
+    let canvas = Canvas::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .marker(Marker::Braille)
+        .x_bounds([0.0, SCOPE_FRAMES as f64])
+        .y_bounds([-1.0, 1.0])
+        .paint(|ctx| {
+            ctx.draw(&canvas::Line::new(
+                0.0,
+                0.0,
+                SCOPE_FRAMES as f64,
+                0.0,
+                Color::DarkGray,
+            ));
+            ctx.layer();
+            for (trace, color) in &traces {
+                // One point per dot across, joined up so steep edges
+                // don't break into dots.
+                let points: Vec<(f64, f64)> = (0..dots.max(2))
+                    .map(|dot| {
+                        let i = dot * (SCOPE_FRAMES - 1) / (dots.max(2) - 1);
+                        (i as f64, trace[start + i] as f64)
+                    })
+                    .collect();
+                for pair in points.windows(2) {
+                    let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+                    ctx.draw(&canvas::Line::new(x1, y1, x2, y2, *color));
+                }
+                ctx.layer();
+            }
+        });
+    frame.render_widget(canvas, area);
+}
 
-            let hue_factor = i as f32 / num_bars as f32;
-            let intensity = h as f32 / bar_height.max(1) as f32;
+/// Plots the left channel against the right, or mid against side so that
+/// mono runs straight up, with a phase correlation meter underneath.
+fn render_vectorscope(
+    frame: &mut Frame,
+    area: Rect,
+    state: &UIState,
+    spectrum: &Arc<Mutex<SpectrumAnalyzer>>,
+) {
+    let title = if state.mid_side {
+        "Vectorscope · M/S"
+    } else {
+        "Vectorscope · L/R"
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let [plot, meter] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
 
-            let color = if intensity > 0.8 {
-                Color::Red
-            } else if intensity > 0.5 {
-                if hue_factor < 0.33 {
-                    Color::Magenta
-                } else if hue_factor < 0.66 {
-                    waveform_color
-                } else {
-                    Color::Green
-                }
-            } else {
-                waveform_color
-            };
+    // Mono files show as a perfectly correlated line.
+    let pairs: Vec<(f32, f32)> = match recent_samples(spectrum, VECTORSCOPE_FRAMES) {
+        Some((samples, channels)) => samples
+            .chunks_exact(channels)
+            .map(|frame| (frame[0], frame[1.min(channels - 1)]))
+            .collect(),
+        None => Vec::new(),
+    };
 
-            for w in 0..bar_width {
-                let x = x_pos + w;
-                if x < width {
-                    let cell = &mut frame.buffer_mut()[(inner.x + x as u16, inner.y + y as u16)];
-                    cell.set_symbol("█");
-                    cell.set_fg(color);
-                }
+    let points: Vec<(f64, f64)> = pairs
+        .iter()
+        .map(|&(left, right)| {
+            if state.mid_side {
+                ((left - right) as f64 / 2.0, (left + right) as f64 / 2.0)
+            } else {
+                (left as f64, right as f64)
             }
-        }
+        })
+        .collect();
+
+    // Square, as a cell holds 2×4 dots and is about twice as tall as wide.
+    let side = plot.width.min(plot.height * 2);
+    let square = Rect::new(plot.x + (plot.width - side) / 2, plot.y, side, plot.height);
+    let color = match state.state {
+        PlaybackState::Playing => Color::Cyan,
+        PlaybackState::Paused => Color::Yellow,
+    };
+    let canvas = Canvas::default()
+        .marker(Marker::Braille)
+        .x_bounds([-1.0, 1.0])
+        .y_bounds([-1.0, 1.0])
+        .paint(|ctx| {
+            ctx.draw(&canvas::Line::new(-1.0, 0.0, 1.0, 0.0, Color::DarkGray));
+            ctx.draw(&canvas::Line::new(0.0, -1.0, 0.0, 1.0, Color::DarkGray));
+            ctx.layer();
+            ctx.draw(&Points {
+                coords: &points,
+                color,
+            });
+        });
+    frame.render_widget(canvas, square);
+
+    render_correlation(frame, meter, correlation(&pairs));
+}
+
+/// Pearson correlation of the left and right channels: +1 is mono, 0 is
+/// unrelated, and −1 is one channel inverted against the other.
+fn correlation(pairs: &[(f32, f32)]) -> f32 {
+    let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+    for &(left, right) in pairs {
+        lr += left * right;
+        ll += left * left;
+        rr += right * right;
+    }
+    if ll == 0.0 || rr == 0.0 {
+        // Silence in either channel says nothing about phase.
+        return 0.0;
     }
+    lr / (ll * rr).sqrt()
 }
 
-fn render_simple_waveform(frame: &mut Frame, area: Rect, state: &UIState) {
-    let width = area.width.saturating_sub(2) as usize;
-    let waveform_data: Vec<u64> = if state.waveform.samples.len() >= width {
-        state.waveform.samples[..width]
-            .iter()
-            .map(|&v| (v * 100.0) as u64)
-            .collect()
+fn render_correlation(frame: &mut Frame, area: Rect, correlation: f32) {
+    let labels = ("−1 ", format!(" +1 {:+.2}", correlation));
+    let width = area
+        .width
+        .saturating_sub(3 + labels.1.chars().count() as u16) as usize;
+    if width < 3 {
+        return;
+    }
+
+    let marker = ((correlation + 1.0) / 2.0 * (width - 1) as f32).round() as usize;
+    let color = if correlation < 0.0 {
+        Color::Red
+    } else if correlation < 0.5 {
+        Color::Yellow
     } else {
-        let scale = width as f32 / state.waveform.samples.len() as f32;
-        (0..width)
-            .map(|i| {
-                let idx = (i as f32 / scale) as usize;
-                if idx < state.waveform.samples.len() {
-                    (state.waveform.samples[idx] * 100.0) as u64
-                } else {
-                    0
-                }
-            })
-            .collect()
+        Color::Green
     };
+    let track: String = (0..width)
+        .map(|i| if i == (width - 1) / 2 { '┼' } else { '─' })
+        .collect();
+    let (before, after) = track.split_at(track.char_indices().nth(marker).map_or(0, |(i, _)| i));
+    let after: String = after.chars().skip(1).collect();
+
+    let line = Line::from(vec![
+        Span::raw(labels.0),
+        Span::styled(before.to_string(), Style::default().fg(Color::DarkGray)),
+        Span::styled("●", Style::default().fg(color)),
+        Span::styled(after, Style::default().fg(Color::DarkGray)),
+        Span::raw(labels.1),
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn render_simple_waveform(frame: &mut Frame, area: Rect, state: &UIState, view: &WaveformView) {
+    let width = area.width as usize;
+    let waveform_data: Vec<u64> = view
+        .bars(&state.waveform, width)
+        .iter()
+        .map(|bar| (bar.rms * 1000.0) as u64)
+        .collect();
+    // Scaled to the loudest part of the whole track, so zooming in on a
+    // quiet passage doesn't blow it up.
+    let loudest = state
+        .waveform
+        .resample(width)
+        .iter()
+        .map(|bar| (bar.rms * 1000.0) as u64)
+        .max()
+        .unwrap_or(0);
 
     let waveform_color = match state.state {
         PlaybackState::Playing => Color::Cyan,
@@ -181,83 +1237,179 @@ fn render_simple_waveform(frame: &mut Frame, area: Rect, state: &UIState) {
     };
 
     let sparkline = Sparkline::default()
-        .block(Block::default().borders(Borders::ALL).title("Waveform"))
         .data(&waveform_data)
+        .max(loudest.max(1))
         .style(Style::default().fg(waveform_color));
 
     frame.render_widget(sparkline, area);
 }
 
-fn render_enhanced_waveform(frame: &mut Frame, area: Rect, state: &UIState) {
-    let waveform_color = match state.state {
+/// A one-row outline of the whole track with the part in view highlighted.
+fn render_overview(frame: &mut Frame, area: Rect, state: &UIState, view: &WaveformView) {
+    const LEVELS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+    let played = match state.state {
         PlaybackState::Playing => Color::Cyan,
         PlaybackState::Paused => Color::Yellow,
     };
 
-    let position_secs = state.position.as_secs();
-    let duration_secs = state.duration.as_secs().max(1);
-    let progress_ratio = position_secs as f64 / duration_secs as f64;
+    let width = area.width as usize;
+    let playhead = (view.playhead * width as f64) as usize;
+    for (x, bar) in state.waveform.resample(width).iter().enumerate() {
+        let fraction = (x as f64 + 0.5) / width as f64;
+        let color = if x == playhead.min(width - 1) {
+            played
+        } else if fraction >= view.start && fraction < view.start + view.span {
+            Color::White
+        } else {
+            Color::DarkGray
+        };
 
-    let block = Block::default().borders(Borders::ALL).title("Waveform");
-    let inner = block.inner(area);
-    frame.render_widget(block, area);
+        let level = (bar.max.max(-bar.min) * LEVELS.len() as f32) as usize;
+        let cell = &mut frame.buffer_mut()[(area.x + x as u16, area.y)];
+        cell.set_symbol(LEVELS[level.min(LEVELS.len() - 1)])
+            .set_fg(color);
+    }
+}
+
+/// Draws the peak envelope of each bar with its RMS body in a brighter shade
+/// inside, at half-cell resolution: every cell holds two rows of the plot,
+/// the upper one in the foreground of `▀` and the lower one in its
+/// background. With `split_channels` a stereo file gets its left channel
+/// above the centre line and its right below, and anything wider a lane
+/// per channel.
+fn render_enhanced_waveform(frame: &mut Frame, inner: Rect, state: &UIState, view: &WaveformView) {
+    // (envelope, body) for the played part of the track.
+    let played = match state.state {
+        PlaybackState::Playing => (Color::Cyan, Color::LightCyan),
+        PlaybackState::Paused => (Color::Yellow, Color::LightYellow),
+    };
+    let unplayed = (Color::DarkGray, Color::Gray);
+    let clipped = (Color::Red, Color::LightRed);
+    let out_of_phase = (Color::Magenta, Color::LightMagenta);
 
     let width = inner.width as usize;
     let height = inner.height as usize;
-    let center = height / 2;
+    let rows = height * 2;
 
-    let waveform_data: Vec<f32> = if state.waveform.samples.len() >= width {
-        state.waveform.samples[..width].to_vec()
+    let channels = if state.split_channels && state.waveform.channels() > 1 {
+        view.channel_bars(&state.waveform, width)
     } else {
-        let scale = width as f32 / state.waveform.samples.len() as f32;
-        (0..width)
-            .map(|i| {
-                let idx = (i as f32 / scale) as usize;
-                if idx < state.waveform.samples.len() {
-                    state.waveform.samples[idx]
-                } else {
-                    0.0
-                }
-            })
+        Vec::new()
+    };
+    // Rows of the plot where each lane starts, plus the end.
+    let lanes: Vec<usize> = if channels.len() > 2 {
+        (0..=channels.len())
+            .map(|i| i * rows / channels.len())
             .collect()
+    } else {
+        vec![0, rows]
     };
+    let center_cells: Vec<usize> = lanes
+        .windows(2)
+        .map(|lane| (lane[0] + lane[1]) / 4)
+        .collect();
 
-    let cursor_pos = (progress_ratio * width as f64) as usize;
+    let cursor_pos = view.cursor(width);
+    let mut column = vec![None; rows];
 
-    for (x, &amplitude) in waveform_data.iter().enumerate() {
-        let bar_height = (amplitude * center as f32) as usize;
-        let color = if x <= cursor_pos {
-            waveform_color
-        } else {
-            Color::DarkGray
+    for (x, bar) in view.bars(&state.waveform, width).iter().enumerate() {
+        // `paired` is set for the first two channels, which the mix's
+        // correlation is measured between.
+        let colors = |clipped_samples: bool, paired: bool| {
+            if clipped_samples {
+                clipped
+            } else if paired && bar.correlation < OUT_OF_PHASE {
+                out_of_phase
+            } else if x <= cursor_pos {
+                played
+            } else {
+                unplayed
+            }
         };
 
-        for y in 0..bar_height.min(center) {
-            let top_y = center.saturating_sub(y + 1);
-            let bottom_y = center + y;
-
-            if top_y < height {
-                let cell = &mut frame.buffer_mut()[(inner.x + x as u16, inner.y + top_y as u16)];
-                cell.set_symbol("█");
-                cell.set_fg(color);
+        match &channels[..] {
+            [left, right] => {
+                let (upper, lower) = column.split_at_mut(rows / 2);
+                plot_level(upper, &left[x], colors(left[x].clipped, true), false);
+                plot_level(lower, &right[x], colors(right[x].clipped, true), true);
             }
-            if bottom_y < height {
-                let cell = &mut frame.buffer_mut()[(inner.x + x as u16, inner.y + bottom_y as u16)];
-                cell.set_symbol("█");
-                cell.set_fg(color);
+            [] => plot_bar(&mut column, bar, colors(bar.clipped, false)),
+            _ => {
+                for (i, (lane, channel)) in lanes.windows(2).zip(&channels).enumerate() {
+                    let colors = colors(channel[x].clipped, i < 2);
+                    plot_bar(&mut column[lane[0]..lane[1]], &channel[x], colors);
+                }
             }
         }
-    }
 
-    if center < height {
-        for x in 0..width {
-            let cell = &mut frame.buffer_mut()[(inner.x + x as u16, inner.y + center as u16)];
-            cell.set_symbol("─");
-            cell.set_fg(Color::DarkGray);
+        for y in 0..height {
+            let cell = &mut frame.buffer_mut()[(inner.x + x as u16, inner.y + y as u16)];
+            match (column[y * 2], column[y * 2 + 1]) {
+                (Some(top), Some(bottom)) if top == bottom => {
+                    cell.set_symbol("█").set_fg(top);
+                }
+                (Some(top), Some(bottom)) => {
+                    cell.set_symbol("▀").set_fg(top).set_bg(bottom);
+                }
+                (Some(top), None) => {
+                    cell.set_symbol("▀").set_fg(top);
+                }
+                (None, Some(bottom)) => {
+                    cell.set_symbol("▄").set_fg(bottom);
+                }
+                (None, None) if center_cells.contains(&y) => {
+                    cell.set_symbol("─").set_fg(Color::DarkGray);
+                }
+                (None, None) => {}
+            }
         }
     }
 }
 
+/// Plots `bar` over `rows`, from full scale at the top to negative full
+/// scale at the bottom, as `(envelope, body)` colours.
+fn plot_bar(rows: &mut [Option<Color>], bar: &WaveformBar, (envelope, body): (Color, Color)) {
+    let len = rows.len() as f32;
+    for (row, color) in rows.iter_mut().enumerate() {
+        // A row is drawn if any of the range it covers is, so quiet audio
+        // still shows up next to the centre line.
+        let top = 1.0 - row as f32 * 2.0 / len;
+        let bottom = 1.0 - (row + 1) as f32 * 2.0 / len;
+        *color = if bottom >= bar.max || top <= bar.min {
+            None
+        } else if bottom < bar.rms && top > -bar.rms {
+            Some(body)
+        } else {
+            Some(envelope)
+        };
+    }
+}
+
+/// Plots the level of `bar` growing away from the centre line, which is
+/// below `rows`, or above them when `downward`.
+fn plot_level(
+    rows: &mut [Option<Color>],
+    bar: &WaveformBar,
+    (envelope, body): (Color, Color),
+    downward: bool,
+) {
+    let len = rows.len();
+    let peak = bar.max.max(-bar.min);
+    for (row, color) in rows.iter_mut().enumerate() {
+        let distance = if downward { row } else { len - 1 - row };
+        // Where the row starts, counting from the centre line.
+        let value = distance as f32 / len as f32;
+        *color = if value >= peak {
+            None
+        } else if value < bar.rms {
+            Some(body)
+        } else {
+            Some(envelope)
+        };
+    }
+}
+
 fn render_title(frame: &mut Frame, area: Rect, state: &UIState) {
     let status_symbol = match state.state {
         PlaybackState::Playing => "▶",
@@ -269,7 +1421,7 @@ fn render_title(frame: &mut Frame, area: Rect, state: &UIState) {
         PlaybackState::Paused => Color::Yellow,
     };
 
-    let title = Paragraph::new(Line::from(vec![
+    let mut spans = vec![
         Span::styled(
             status_symbol,
             Style::default()
@@ -277,21 +1429,82 @@ fn render_title(frame: &mut Frame, area: Rect, state: &UIState) {
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" "),
-        Span::styled(
-            &state.filename,
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-    ]))
-    .block(
-        Block::default().borders(Borders::ALL).title(Span::styled(
-            "apz",
-            Style::default()
-                .fg(Color::Magenta)
-                .add_modifier(Modifier::BOLD),
-        )),
-    );
+    ];
+
+    let mode_style = Style::default().fg(Color::Magenta);
+    if state.shuffle {
+        spans.push(Span::styled("⇄ ", mode_style));
+    }
+    match state.repeat {
+        RepeatMode::Off => {}
+        RepeatMode::All => spans.push(Span::styled("↻ ", mode_style)),
+        RepeatMode::One => spans.push(Span::styled("↻1 ", mode_style)),
+    }
+
+    if state.track_count > 1 {
+        spans.push(Span::styled(
+            format!("{}/{} ", state.track_position + 1, state.track_count),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    let title_style = Style::default()
+        .fg(Color::Cyan)
+        .add_modifier(Modifier::BOLD);
+    let metadata = &state.metadata;
+
+    match &metadata.title {
+        Some(title) => {
+            if let Some(artist) = &metadata.artist {
+                spans.push(Span::styled(artist, Style::default().fg(Color::Cyan)));
+                spans.push(Span::raw(" — "));
+            }
+            spans.push(Span::styled(title, title_style));
+
+            let album_info: Vec<&str> = [metadata.album.as_deref(), metadata.year.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect();
+            if !album_info.is_empty() {
+                spans.push(Span::styled(
+                    format!(" ({})", album_info.join(", ")),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+        }
+        None => spans.push(Span::styled(&state.title, title_style)),
+    }
+
+    let mut lines = vec![Line::from(spans)];
+    if state.show_info {
+        let mut details = Vec::new();
+        if let Some(number) = metadata.track_number {
+            details.push(format!("Track {}", number));
+        }
+        let summary = metadata.technical_summary();
+        if !summary.is_empty() {
+            details.push(summary);
+        }
+        lines.push(Line::from(Span::styled(
+            details.join(" · "),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    let mut block = Block::default().borders(Borders::ALL).title(Span::styled(
+        "apz",
+        Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD),
+    ));
+
+    if let Some(message) = state.current_message() {
+        block = block.title(
+            Line::from(Span::styled(message, Style::default().fg(Color::Green))).right_aligned(),
+        );
+    }
+
+    let title = Paragraph::new(lines).block(block);
 
     frame.render_widget(title, area);
 }
@@ -335,51 +1548,79 @@ fn render_volume(frame: &mut Frame, area: Rect, state: &UIState) {
     frame.render_widget(gauge, area);
 }
 
-fn render_controls(frame: &mut Frame, area: Rect) {
+/// Returns where the clickable hints ended up and the key each stands for.
+fn render_controls(frame: &mut Frame, area: Rect) -> Vec<(Rect, char)> {
+    // Clicking one of these, or the word after it, works like the key.
+    let clickable = [
+        ("[Space]", " play/pause  ", ' '),
+        ("[Q]", " quit  ", 'q'),
+        ("[R]", " restart  ", 'r'),
+        ("[V]", " view  ", 'v'),
+        ("[F]", " full screen", 'f'),
+    ];
+
+    let inner = area.inner(Margin::new(1, 1));
+    let mut hints = Vec::new();
+    let mut first_line = Vec::new();
+    let mut x = inner.x;
+    for (hint, label, key) in clickable {
+        let hint = Span::styled(
+            hint,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
+        let label = Span::raw(label);
+        let width = (hint.width() + label.width()) as u16;
+        hints.push((Rect::new(x, inner.y, width, 1).intersection(inner), key));
+        x = x.saturating_add(width);
+        first_line.extend([hint, label]);
+    }
+
     let controls = Paragraph::new(vec![
+        Line::from(first_line),
         Line::from(vec![
             Span::styled(
-                "[Space]",
+                "[←/→]",
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD),
             ),
-            Span::raw(" play/pause  "),
+            Span::raw(" seek ±5s  "),
             Span::styled(
-                "[Q]",
+                "[↑/↓]",
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD),
             ),
-            Span::raw(" quit  "),
+            Span::raw(" volume ±5%  "),
             Span::styled(
-                "[R]",
+                "[N/P]",
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD),
             ),
-            Span::raw(" restart"),
-        ]),
-        Line::from(vec![
+            Span::raw(" next/prev  "),
             Span::styled(
-                "[←/→]",
+                "[S/L]",
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD),
             ),
-            Span::raw(" seek ±5s  "),
+            Span::raw(" shuffle/repeat  "),
             Span::styled(
-                "[↑/↓]",
+                "[W]",
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD),
             ),
-            Span::raw(" volume ±5%"),
+            Span::raw(" save queue"),
         ]),
     ])
     .block(Block::default().borders(Borders::ALL).title("Controls"));
 
     frame.render_widget(controls, area);
+    hints
 }
 
 fn format_duration(duration: Duration) -> String {
@@ -388,3 +1629,46 @@ fn format_duration(duration: Duration) -> String {
     let seconds = secs % 60;
     format!("{:02}:{:02}", minutes, seconds)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_follow_the_bars_frequencies() {
+        // The first two bars share a bin.
+        let frequencies = [47.0, 47.0, 94.0, 188.0];
+
+        assert_eq!(bar_position(&frequencies, 40.0), None);
+        assert_eq!(bar_position(&frequencies, 47.0), Some(1.5));
+        assert_eq!(bar_position(&frequencies, 94.0), Some(2.5));
+        let between = bar_position(&frequencies, 132.9).unwrap();
+        assert!((between - 3.0).abs() < 0.01);
+        assert_eq!(bar_position(&frequencies, 188.0), Some(3.5));
+        assert_eq!(bar_position(&frequencies, 200.0), None);
+        assert_eq!(bar_position(&[], 100.0), None);
+    }
+
+    #[test]
+    fn fitted_slots_show_the_loudest_bar() {
+        let bar = |level| {
+            let mut bar = Bar::default();
+            bar.level = level;
+            bar.peak = level;
+            bar
+        };
+        let bars = [bar(0.1), bar(0.7), bar(0.2), bar(0.4)];
+
+        let slots = fit_bars(&bars, 2);
+        assert_eq!(slots[0].index, 1);
+        assert_eq!(slots[1].index, 3);
+        assert_eq!(slots[1].bar.level, 0.4);
+
+        let wide = fit_bars(&bars, 8);
+        assert_eq!(
+            wide.iter().map(|s| s.index).collect::<Vec<_>>(),
+            [0, 0, 1, 1, 2, 2, 3, 3]
+        );
+        assert_eq!(fit_bars(&[], 3).len(), 3);
+    }
+}
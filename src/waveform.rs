@@ -1,96 +1,592 @@
 use rodio::{Decoder, Source};
 use std::fs::File;
 use std::io::BufReader;
+use std::ops::Range;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::peak_cache::PeakCache;
+
+/// How the waveform panel draws the waveform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaveformStyle {
+    /// A sparkline of the loudness.
+    Simple,
+    /// The peak envelope around the centre line, with the loudness inside.
+    Enhanced,
+}
+
+impl WaveformStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "simple" => Some(WaveformStyle::Simple),
+            "enhanced" => Some(WaveformStyle::Enhanced),
+            _ => None,
+        }
+    }
+
+    pub fn toggle(self) -> Self {
+        match self {
+            WaveformStyle::Simple => WaveformStyle::Enhanced,
+            WaveformStyle::Enhanced => WaveformStyle::Simple,
+        }
+    }
+}
+
+/// Samples at or above this magnitude count as clipped.
+const CLIP_LEVEL: f32 = 0.999;
+
+/// One column of the waveform, summarizing a run of samples across all
+/// channels. Values are relative to full scale, so `-1.0..=1.0`.
+#[derive(Clone, Copy, Default)]
+pub struct WaveformBar {
+    pub min: f32,
+    pub max: f32,
+    pub rms: f32,
+    /// Some sample in the run reached full scale.
+    pub clipped: bool,
+    /// How alike the first two channels are over the run, from 1.0 (the
+    /// same) through 0.0 (unrelated) to -1.0 (one is the other inverted).
+    /// Always 0.0 for a single channel.
+    pub correlation: f32,
+}
+
+impl WaveformBar {
+    /// A single bar covering all of `bars`.
+    fn merge(bars: &[WaveformBar]) -> WaveformBar {
+        if bars.is_empty() {
+            return WaveformBar::default();
+        }
+        let mean_square = bars.iter().map(|bar| bar.rms * bar.rms).sum::<f32>() / bars.len() as f32;
+        WaveformBar {
+            min: bars.iter().map(|bar| bar.min).fold(0.0, f32::min),
+            max: bars.iter().map(|bar| bar.max).fold(0.0, f32::max),
+            rms: mean_square.sqrt(),
+            clipped: bars.iter().any(|bar| bar.clipped),
+            correlation: bars.iter().map(|bar| bar.correlation).sum::<f32>() / bars.len() as f32,
+        }
+    }
+}
+
+/// Peaks for all channels together and for each on its own, lined up.
+#[derive(Clone, Default)]
+pub struct Peaks {
+    /// All channels together.
+    pub mix: Vec<WaveformBar>,
+    /// Each channel on its own, as long as `mix`.
+    pub channels: Vec<Vec<WaveformBar>>,
+}
+
+/// Bars in each chunk of a `Bars`.
+const CHUNK_BARS: usize = 4096;
+
+/// An append-only run of bars, kept in chunks behind `Arc`s. A clone shares
+/// the chunks, and only the last one, while it's still being filled, gets
+/// copied when either side appends to it. That keeps handing the UI the
+/// waveform so far cheap however long the file is.
+#[derive(Clone, Default)]
+struct Bars {
+    chunks: Vec<Arc<Vec<WaveformBar>>>,
+    len: usize,
+}
+
+impl Bars {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, bar: WaveformBar) {
+        if self.len.is_multiple_of(CHUNK_BARS) {
+            self.chunks.push(Arc::new(Vec::with_capacity(CHUNK_BARS)));
+        }
+        if let Some(chunk) = self.chunks.last_mut() {
+            Arc::make_mut(chunk).push(bar);
+        }
+        self.len += 1;
+    }
+
+    /// Copies out the bars in `range`, which is cut short at the end.
+    fn to_vec(&self, range: Range<usize>) -> Vec<WaveformBar> {
+        let (start, end) = (range.start.min(self.len), range.end.min(self.len));
+        let mut bars = Vec::with_capacity(end.saturating_sub(start));
+        let mut i = start;
+        while i < end {
+            let chunk = &self.chunks[i / CHUNK_BARS];
+            let from = i % CHUNK_BARS;
+            let to = chunk.len().min(from + end - i);
+            bars.extend_from_slice(&chunk[from..to]);
+            i += to - from;
+        }
+        bars
+    }
+}
+
+/// One level of a `PeakPyramid`, laid out like `Peaks`.
+#[derive(Clone, Default)]
+struct PeakLevel {
+    mix: Bars,
+    channels: Vec<Bars>,
+}
+
+impl PeakLevel {
+    fn push(&mut self, mix: WaveformBar, channels: &[WaveformBar]) {
+        self.mix.push(mix);
+        if self.channels.len() < channels.len() {
+            self.channels.resize(channels.len(), Bars::default());
+        }
+        for (bars, &bar) in self.channels.iter_mut().zip(channels) {
+            bars.push(bar);
+        }
+    }
+
+    /// Merges the bars from `start` to the end into one.
+    fn merge_tail(&self, start: usize) -> (WaveformBar, Vec<WaveformBar>) {
+        let end = self.mix.len();
+        let mix = WaveformBar::merge(&self.mix.to_vec(start..end));
+        let channels = self
+            .channels
+            .iter()
+            .map(|bars| WaveformBar::merge(&bars.to_vec(start..end)))
+            .collect();
+        (mix, channels)
+    }
+}
+
+/// The peaks as read, plus coarser copies where each level merges pairs of
+/// bars from the one below. Drawing any stretch of the track then only
+/// takes about as many bars as there are columns, however far it is zoomed.
+#[derive(Clone, Default)]
+struct PeakPyramid {
+    levels: Vec<PeakLevel>,
+}
+
+impl PeakPyramid {
+    fn from_peaks(peaks: Peaks) -> Self {
+        let mut pyramid = PeakPyramid::default();
+        for (i, &mix) in peaks.mix.iter().enumerate() {
+            let channels: Vec<WaveformBar> = peaks.channels.iter().map(|bars| bars[i]).collect();
+            pyramid.push(mix, channels);
+        }
+        pyramid.finish();
+        pyramid
+    }
+
+    /// Appends a peak read from the file, merging it upwards whenever it
+    /// completes a pair.
+    fn push(&mut self, mut mix: WaveformBar, mut channels: Vec<WaveformBar>) {
+        let mut level = 0;
+        loop {
+            if level == self.levels.len() {
+                self.levels.push(PeakLevel::default());
+            }
+            let peaks = &mut self.levels[level];
+            peaks.push(mix, &channels);
+
+            let len = peaks.mix.len();
+            if len % 2 == 1 {
+                break;
+            }
+            (mix, channels) = peaks.merge_tail(len - 2);
+            level += 1;
+        }
+    }
+
+    /// Carries a trailing unpaired bar up through the levels once the whole
+    /// file has been read, so the coarse levels reach the end as well.
+    fn finish(&mut self) {
+        let mut level = 1;
+        while level < self.levels.len() {
+            let below = &self.levels[level - 1];
+            let covered = self.levels[level].mix.len() * 2;
+            if covered < below.mix.len() {
+                let (mix, channels) = below.merge_tail(covered);
+                self.levels[level].push(mix, &channels);
+            }
+            level += 1;
+        }
+    }
+
+    fn channels(&self) -> usize {
+        self.levels.first().map_or(0, |base| base.channels.len())
+    }
+
+    /// The peaks as read, in one piece, for the cache.
+    fn base_peaks(&self) -> Option<Peaks> {
+        let base = self.levels.first()?;
+        let len = base.mix.len();
+        Some(Peaks {
+            mix: base.mix.to_vec(0..len),
+            channels: base
+                .channels
+                .iter()
+                .map(|bars| bars.to_vec(0..len))
+                .collect(),
+        })
+    }
+}
 
 #[derive(Clone)]
 pub struct WaveformData {
-    pub samples: Vec<f32>,
-    pub enhanced: bool,
+    pyramid: Arc<PeakPyramid>,
+    // The stored peaks this waveform covers. Its end may lie beyond what
+    // has been read so far.
+    range: Range<usize>,
 }
 
 impl WaveformData {
-    pub fn new(samples: Vec<f32>, enhanced: bool) -> Self {
-        Self { samples, enhanced }
+    /// A flat waveform, until the peaks come in.
+    pub fn empty() -> Self {
+        Self {
+            pyramid: Arc::default(),
+            range: 0..0,
+        }
+    }
+
+    /// How many channels there are, once the first peaks have been read.
+    pub fn channels(&self) -> usize {
+        self.pyramid.channels()
+    }
+
+    /// Stored peaks in the waveform: the most columns it can be drawn in
+    /// without repeating bars.
+    pub fn peak_count(&self) -> usize {
+        self.range.len()
+    }
+
+    /// The part of the waveform between `from` and `to`, given as fractions
+    /// of the whole, e.g. one cue track out of a single-file rip.
+    pub fn slice(&self, from: f64, to: f64) -> WaveformData {
+        let (start, end) = self.peak_range(from, to);
+        WaveformData {
+            pyramid: Arc::clone(&self.pyramid),
+            range: start..end,
+        }
+    }
+
+    /// The whole waveform fitted to `width` columns.
+    pub fn resample(&self, width: usize) -> Vec<WaveformBar> {
+        self.window(0.0, 1.0, width)
+    }
+
+    /// The part between `from` and `to` (fractions of the whole) fitted to
+    /// `width` columns: bars are merged when there are more than that, and
+    /// repeated when there are fewer.
+    pub fn window(&self, from: f64, to: f64, width: usize) -> Vec<WaveformBar> {
+        self.window_of(from, to, width, |peaks| &peaks.mix)
+    }
+
+    /// Like `window`, for each channel.
+    pub fn window_channels(&self, from: f64, to: f64, width: usize) -> Vec<Vec<WaveformBar>> {
+        (0..self.channels())
+            .map(|channel| self.window_of(from, to, width, |peaks| &peaks.channels[channel]))
+            .collect()
+    }
+
+    fn window_of(
+        &self,
+        from: f64,
+        to: f64,
+        width: usize,
+        bars: impl Fn(&PeakLevel) -> &Bars,
+    ) -> Vec<WaveformBar> {
+        let (start, end) = self.peak_range(from, to);
+
+        // The coarsest level that still has a bar per column.
+        let per_column = (end - start) / width.max(1);
+        let level =
+            (per_column.max(1).ilog2() as usize).min(self.pyramid.levels.len().saturating_sub(1));
+        let Some(peaks) = self.pyramid.levels.get(level) else {
+            return vec![WaveformBar::default(); width];
+        };
+
+        let start = start >> level;
+        let end = (end + (1 << level) - 1) >> level;
+        resample(&bars(peaks).to_vec(start..end), end - start, width)
+    }
+
+    /// `from..to` (fractions of the waveform) in stored peaks, never empty.
+    fn peak_range(&self, from: f64, to: f64) -> (usize, usize) {
+        let len = self.range.len() as f64;
+        let start = self.range.start + (from.clamp(0.0, 1.0) * len) as usize;
+        let end = self.range.start + (to.clamp(0.0, 1.0) * len).ceil() as usize;
+        (start, end.max(start + 1))
     }
 }
 
-pub fn generate_waveform<P: AsRef<Path>>(
-    path: P,
-    target_width: usize,
-    enhanced: bool,
-) -> Result<WaveformData, Box<dyn std::error::Error>> {
-    let width = if enhanced {
-        target_width * 2
-    } else {
-        target_width
-    };
-    generate_waveform_internal(path, width, enhanced)
+/// How often the worker hands a partial waveform to the UI.
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Frames summarized by each stored peak, about 12 ms at 44.1 kHz. This is
+/// as far as the waveform can be zoomed in.
+const FRAMES_PER_PEAK: usize = 512;
+
+/// A waveform that a worker thread fills in, left to right, while the track
+/// plays. Clones share the same worker, which stops early once every clone
+/// has been dropped.
+#[derive(Clone)]
+pub struct WaveformJob {
+    shared: Arc<Mutex<JobState>>,
+}
+
+struct JobState {
+    data: WaveformData,
+    // Bumped every time `data` is replaced.
+    version: u64,
 }
 
-fn generate_waveform_internal<P: AsRef<Path>>(
-    path: P,
-    target_width: usize,
-    enhanced: bool,
-) -> Result<WaveformData, Box<dyn std::error::Error>> {
+impl WaveformJob {
+    pub fn version(&self) -> u64 {
+        self.shared.lock().unwrap().version
+    }
+
+    pub fn data(&self) -> WaveformData {
+        self.shared.lock().unwrap().data.clone()
+    }
+}
+
+/// Starts generating the waveform of `path` on a worker thread. It starts
+/// out flat; a file that can't be decoded stays that way.
+pub fn spawn_waveform<P: AsRef<Path>>(path: P, cache: Option<PeakCache>) -> WaveformJob {
+    let shared = Arc::new(Mutex::new(JobState {
+        data: WaveformData::empty(),
+        version: 0,
+    }));
+    let worker = Arc::clone(&shared);
+    let path = path.as_ref().to_path_buf();
+    thread::spawn(move || stream_waveform(&path, cache, &worker).ok());
+
+    WaveformJob { shared }
+}
+
+/// Reads the peaks from the cache, or decodes the file one peak at a time
+/// (so memory use doesn't depend on the length of the file) and caches them.
+fn stream_waveform(
+    path: &Path,
+    cache: Option<PeakCache>,
+    shared: &Arc<Mutex<JobState>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(peaks) = cache
+        .as_ref()
+        .and_then(|cache| cache.load(path, FRAMES_PER_PEAK as u32))
+    {
+        let total = peaks.mix.len();
+        publish(shared, PeakPyramid::from_peaks(peaks), total);
+        return Ok(());
+    }
+
     let file = File::open(path)?;
     let source = Decoder::new(BufReader::new(file))?;
 
-    let channels = source.channels();
-    let samples: Vec<i16> = source.convert_samples().collect();
+    let channels = source.channels().max(1) as usize;
+    let sample_rate = source.sample_rate() as f64;
+    // Without a length up front the peaks read so far are stretched to fit.
+    let expected_peaks = source
+        .total_duration()
+        .map(|duration| (duration.as_secs_f64() * sample_rate) as usize / FRAMES_PER_PEAK)
+        .filter(|&peaks| peaks > 0);
+
+    let mut pyramid = PeakPyramid::default();
+    let mut peak = PeakAccumulator::new(channels);
+    let mut read = 0;
+    let mut last_publish = Instant::now();
+
+    for sample in source {
+        peak.add(sample as f32 / i16::MAX as f32);
+        if peak.frames < FRAMES_PER_PEAK {
+            continue;
+        }
+
+        peak.finish(&mut pyramid);
+        read += 1;
 
-    if samples.is_empty() {
-        return Ok(WaveformData::new(vec![0.0; target_width], enhanced));
+        // Nobody is looking any more.
+        if Arc::strong_count(shared) == 1 {
+            return Ok(());
+        }
+        if last_publish.elapsed() >= PUBLISH_INTERVAL {
+            let total = expected_peaks.unwrap_or(0).max(read);
+            // Shares all but the last chunk of each level with `pyramid`.
+            publish(shared, pyramid.clone(), total);
+            last_publish = Instant::now();
+        }
     }
 
-    let total_samples = samples.len() / channels as usize;
-    let samples_per_bar = (total_samples / target_width).max(1);
+    if peak.mix.count > 0 {
+        peak.finish(&mut pyramid);
+        read += 1;
+    }
+    pyramid.finish();
 
-    let mut waveform = Vec::with_capacity(target_width);
+    if let Some(cache) = cache
+        && let Some(peaks) = pyramid.base_peaks()
+    {
+        // A cache that can't be written just means decoding again next time.
+        cache.store(path, FRAMES_PER_PEAK as u32, &peaks).ok();
+    }
+    if read > 0 {
+        publish(shared, pyramid, read);
+    }
 
-    for i in 0..target_width {
-        let start_idx = i * samples_per_bar * channels as usize;
-        let end_idx = ((i + 1) * samples_per_bar * channels as usize).min(samples.len());
+    Ok(())
+}
 
-        if start_idx >= samples.len() {
-            waveform.push(0.0);
-            continue;
+/// Running min, max and sum of squares for one peak of one channel, or of
+/// all of them.
+#[derive(Default)]
+struct Accumulator {
+    min: f32,
+    max: f32,
+    sum_squares: f32,
+    count: usize,
+    clipped: bool,
+}
+
+impl Accumulator {
+    fn add(&mut self, sample: f32) {
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        self.sum_squares += sample * sample;
+        self.count += 1;
+        self.clipped |= sample.abs() >= CLIP_LEVEL;
+    }
+
+    /// The finished peak; the accumulator starts over for the next one.
+    fn finish(&mut self) -> WaveformBar {
+        let peak = std::mem::take(self);
+        WaveformBar {
+            min: peak.min,
+            max: peak.max,
+            rms: (peak.sum_squares / peak.count.max(1) as f32).sqrt(),
+            clipped: peak.clipped,
+            correlation: 0.0,
         }
+    }
+}
 
-        let mut sum = 0.0;
-        let mut count = 0;
+/// The peak being read, fed interleaved samples.
+struct PeakAccumulator {
+    mix: Accumulator,
+    channels: Vec<Accumulator>,
+    // Sum of the products of the first two channels, for their correlation.
+    cross: f32,
+    first: f32,
+    channel: usize,
+    frames: usize,
+}
 
-        for idx in (start_idx..end_idx).step_by(channels as usize) {
-            if idx < samples.len() {
-                let sample = samples[idx] as f32 / i16::MAX as f32;
-                sum += sample.abs();
-                count += 1;
-            }
+impl PeakAccumulator {
+    fn new(channels: usize) -> Self {
+        Self {
+            mix: Accumulator::default(),
+            channels: (0..channels).map(|_| Accumulator::default()).collect(),
+            cross: 0.0,
+            first: 0.0,
+            channel: 0,
+            frames: 0,
+        }
+    }
+
+    fn add(&mut self, sample: f32) {
+        self.mix.add(sample);
+        self.channels[self.channel].add(sample);
+        match self.channel {
+            0 => self.first = sample,
+            1 => self.cross += self.first * sample,
+            _ => {}
         }
 
-        let avg = if count > 0 { sum / count as f32 } else { 0.0 };
-        waveform.push(avg);
+        self.channel += 1;
+        if self.channel == self.channels.len() {
+            self.channel = 0;
+            self.frames += 1;
+        }
     }
 
-    normalize_waveform(&mut waveform);
+    /// Adds the finished peak to `pyramid` and starts over.
+    fn finish(&mut self, pyramid: &mut PeakPyramid) {
+        let correlation = match &self.channels[..] {
+            [left, right, ..] if left.sum_squares > 0.0 && right.sum_squares > 0.0 => {
+                self.cross / (left.sum_squares * right.sum_squares).sqrt()
+            }
+            _ => 0.0,
+        };
+
+        let mut mix = self.mix.finish();
+        mix.correlation = correlation;
+        let channels = self.channels.iter_mut().map(Accumulator::finish).collect();
+        pyramid.push(mix, channels);
 
-    Ok(WaveformData::new(waveform, enhanced))
+        self.cross = 0.0;
+        self.channel = 0;
+        self.frames = 0;
+    }
+}
+
+/// Hands the UI the peaks read so far, as the first part of `total`.
+fn publish(shared: &Mutex<JobState>, pyramid: PeakPyramid, total: usize) {
+    let mut state = shared.lock().unwrap();
+    state.data = WaveformData {
+        pyramid: Arc::new(pyramid),
+        range: 0..total,
+    };
+    state.version += 1;
 }
 
-fn normalize_waveform(waveform: &mut [f32]) {
-    if waveform.is_empty() {
-        return;
+/// Fits `bars`, the first part of `total`, to `width` columns. Columns past
+/// the end of `bars` are left silent.
+fn resample(bars: &[WaveformBar], total: usize, width: usize) -> Vec<WaveformBar> {
+    let total = total.max(1);
+    (0..width)
+        .map(|i| {
+            let start = i * total / width;
+            let end = ((i + 1) * total / width).max(start + 1).min(bars.len());
+            bars.get(start..end)
+                .map(WaveformBar::merge)
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(i: usize) -> WaveformBar {
+        WaveformBar {
+            max: i as f32,
+            ..WaveformBar::default()
+        }
     }
 
-    let max = waveform
-        .iter()
-        .copied()
-        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
-        .unwrap_or(1.0);
+    #[test]
+    fn bars_read_across_chunks() {
+        let mut bars = Bars::default();
+        for i in 0..CHUNK_BARS * 2 + 10 {
+            bars.push(bar(i));
+        }
+
+        let read = bars.to_vec(CHUNK_BARS - 2..CHUNK_BARS * 2 + 20);
+        let maxes: Vec<f32> = read.iter().map(|bar| bar.max).collect();
+        let expected: Vec<f32> = (CHUNK_BARS - 2..CHUNK_BARS * 2 + 10)
+            .map(|i| i as f32)
+            .collect();
+        assert_eq!(maxes, expected);
+    }
 
-    if max > 0.0 {
-        for sample in waveform.iter_mut() {
-            *sample /= max;
+    #[test]
+    fn copies_share_all_but_the_last_chunk() {
+        let mut bars = Bars::default();
+        for i in 0..CHUNK_BARS + 1 {
+            bars.push(bar(i));
         }
+        let published = bars.clone();
+        bars.push(bar(0));
+
+        assert!(Arc::ptr_eq(&bars.chunks[0], &published.chunks[0]));
+        assert!(!Arc::ptr_eq(&bars.chunks[1], &published.chunks[1]));
+        assert_eq!(published.len(), CHUNK_BARS + 1);
+        assert_eq!(published.chunks[1].len(), 1);
     }
 }